@@ -14,32 +14,64 @@ pub fn generate_ast(output_dir: &str) -> io::Result<()> {
     define_ast(
         output_dir,
         "Expr".to_string(),
+        &["crate::error::*", "crate::token::*"],
         vec![
-            "Binary   : Box<Expr> left, Token operator, Box<Expr> right".to_string(),
-            "Grouping : Box<Expr> expression".to_string(),
-            "Literal  : Option<Literal> value".to_string(),
-            "Unary    : Token operator, Box<Expr> right".to_string(),
+            "Assign   : Token name, Box<Expr> value, usize id, Span span".to_string(),
+            "Binary   : Box<Expr> left, Token operator, Box<Expr> right, Span span".to_string(),
+            "Call     : Box<Expr> callee, Token paren, Vec<Expr> arguments, Span span".to_string(),
+            "Get      : Box<Expr> object, Token name, Span span".to_string(),
+            "Grouping : Box<Expr> expression, Span span".to_string(),
+            "Interpolation : Vec<Expr> parts, Span span".to_string(),
+            "Literal  : Option<Literal> value, Span span".to_string(),
+            "Logical  : Box<Expr> left, Token operator, Box<Expr> right, Span span".to_string(),
+            "Set      : Box<Expr> object, Token name, Box<Expr> value, Span span".to_string(),
+            "Super    : Token keyword, Token method, usize id, Span span".to_string(),
+            "This     : Token keyword, usize id, Span span".to_string(),
+            "Unary    : Token operator, Box<Expr> right, Span span".to_string(),
+            "Variable : Token name, usize id, Span span".to_string(),
+        ],
+    )?;
+    define_ast(
+        output_dir,
+        "Stmt".to_string(),
+        &["crate::error::*", "crate::expr::*", "crate::token::*", "std::rc::Rc"],
+        vec![
+            "Block      : Vec<Stmt> statements".to_string(),
+            "Class      : Token name, Option<Box<Expr>> superclass, Vec<FunctionStmt> methods, Option<String> doc".to_string(),
+            "Expression : Box<Expr> expression".to_string(),
+            "Function   : Token name, Rc<Vec<Token>> params, Rc<Vec<Stmt>> body, Option<String> doc".to_string(),
+            "If         : Box<Expr> condition, Box<Stmt> then_branch, Option<Box<Stmt>> else_branch".to_string(),
+            "Print      : Box<Expr> expression".to_string(),
+            "Return     : Token keyword, Option<Box<Expr>> value".to_string(),
+            "Var        : Token name, Option<Box<Expr>> initializer, Option<String> doc".to_string(),
+            "While      : Box<Expr> condition, Box<Stmt> body".to_string(),
         ],
     )?;
     Ok(())
 }
 
-fn define_ast(output_dir: &str, base_name: String, types: Vec<String>) -> io::Result<()> {
+fn define_ast(
+    output_dir: &str,
+    base_name: String,
+    imports: &[&str],
+    types: Vec<String>,
+) -> io::Result<()> {
     let path = format!("{output_dir}/{}.rs", base_name.to_lowercase());
     let mut file = File::create(path)?;
     let mut tree_types = Vec::new();
 
-    write!(file, "{}", "use crate::error::*;\n")?;
-    write!(file, "{}", "use crate::token::*;\n")?;
+    for import in imports {
+        writeln!(file, "use {import};")?;
+    }
 
     for ttype in types {
-        let (base_class_name, args) = ttype.split_once(":").unwrap();
+        let (base_class_name, args) = ttype.split_once(':').unwrap();
         // Binary + Expr
         let class_name = format!("{}{}", base_class_name.trim(), base_name);
-        let arg_split = args.split(",");
+        let arg_split = args.split(',');
         let mut fields = Vec::new();
         for arg in arg_split {
-            let (t2type, name) = arg.trim().split_once(" ").unwrap();
+            let (t2type, name) = arg.trim().split_once(' ').unwrap();
             fields.push(format!("{}: {}", name, t2type));
         }
         tree_types.push(TreeType {
@@ -49,47 +81,77 @@ fn define_ast(output_dir: &str, base_name: String, types: Vec<String>) -> io::Re
         });
     }
 
-    write!(file, "\npub enum {base_name} {{\n")?;
+    writeln!(file, "\npub enum {base_name} {{")?;
     for t in &tree_types {
-        write!(file, "    {}({}),\n", t.base_class_name, t.class_name)?;
+        writeln!(file, "    {}({}),", t.base_class_name, t.class_name)?;
     }
-    write!(file, "}}\n\n")?;
+    writeln!(file, "}}\n")?;
 
-    write!(file, "impl {} {{\n", base_name)?;
-    write!(file, "    pub fn accept<T>(&self, {}_visitor: &dyn {base_name}Visitor<T>) -> Result<T, LoxError> {{\n", base_name.to_lowercase())?;
-    write!(file, "        match self {{\n")?;
-    for t in &tree_types {
-        write!(
-            file,
-            "            {}::{}(v) => v.accept({}_visitor),\n",
-            base_name,
-            t.base_class_name,
-            base_name.to_lowercase()
-        )?;
+    // Every tree gets a read-only visitor and a `&mut self` one, so stateful
+    // passes such as the interpreter don't need interior mutability.
+    let variants = [
+        ("accept", "Visitor", "&self", "&dyn"),
+        ("accept_mut", "VisitorMut", "&mut self", "&mut dyn"),
+    ];
+
+    writeln!(file, "impl {} {{", base_name)?;
+    for (i, (accept, visitor, _, reference)) in variants.iter().enumerate() {
+        if i > 0 {
+            writeln!(file)?;
+        }
+        writeln!(file, "    pub fn {accept}<T>(&self, {}_visitor: {reference} {base_name}{visitor}<T>) -> Result<T, LoxError> {{", base_name.to_lowercase())?;
+        writeln!(file, "        match self {{")?;
+        for t in &tree_types {
+            writeln!(
+                file,
+                "            {}::{}(v) => v.{accept}({}_visitor),",
+                base_name,
+                t.base_class_name,
+                base_name.to_lowercase()
+            )?;
+        }
+        writeln!(file, "        }}")?;
+        writeln!(file, "    }}")?;
+    }
+    // Trees whose nodes all carry a `span` get an accessor on the enum, so
+    // callers can locate any node without matching on it.
+    if tree_types
+        .iter()
+        .all(|t| t.fields.iter().any(|f| f == "span: Span"))
+    {
+        writeln!(file)?;
+        writeln!(file, "    pub fn span(&self) -> Span {{")?;
+        writeln!(file, "        match self {{")?;
+        for t in &tree_types {
+            writeln!(file, "            {}::{}(v) => v.span,", base_name, t.base_class_name)?;
+        }
+        writeln!(file, "        }}")?;
+        writeln!(file, "    }}")?;
     }
-    write!(file, "        }}\n")?;
-    write!(file, "    }}\n")?;
-    write!(file, "}}\n\n")?;
+    writeln!(file, "}}\n")?;
 
     for t in &tree_types {
-        write!(file, "pub struct {} {{\n", t.class_name)?;
+        writeln!(file, "pub struct {} {{", t.class_name)?;
         for f in &t.fields {
-            write!(file, "    pub {},\n", f)?;
+            writeln!(file, "    pub {},", f)?;
         }
-        write!(file, "}}\n\n")?;
+        writeln!(file, "}}\n")?;
     }
 
-    write!(file, "pub trait ExprVisitor<T> {{\n")?;
-    for t in &tree_types {
-        write!(
-            file,
-            "    fn visit_{}_{}(&self, expr: &{}) -> Result<T, LoxError>;\n",
-            t.base_class_name.to_lowercase(),
-            base_name.to_lowercase(),
-            t.class_name
-        )?;
+    for (_, visitor, receiver, _) in &variants {
+        writeln!(file, "pub trait {base_name}{visitor}<T> {{")?;
+        for t in &tree_types {
+            writeln!(
+                file,
+                "    fn visit_{}_{}({receiver}, {}: &{}) -> Result<T, LoxError>;",
+                t.base_class_name.to_lowercase(),
+                base_name.to_lowercase(),
+                base_name.to_lowercase(),
+                t.class_name
+            )?;
+        }
+        writeln!(file, "}}\n")?;
     }
-    write!(file, "}}\n\n")?;
 
     /*
     impl BinaryExpr {
@@ -98,20 +160,25 @@ fn define_ast(output_dir: &str, base_name: String, types: Vec<String>) -> io::Re
     }
     */
     for t in &tree_types {
-        write!(file, "impl {} {{\n", t.class_name)?;
-        write!(
-            file,
-            "    pub fn accept<T>(&self, visitor: &dyn {}Visitor<T>) -> Result<T, LoxError> {{\n",
-            base_name
-        )?;
-        write!(
-            file,
-            "        visitor.visit_{}_{}(self)\n",
-            t.base_class_name.to_lowercase(),
-            base_name.to_lowercase()
-        )?;
-        write!(file, "    }}\n")?;
-        write!(file, "}}\n\n")?;
+        writeln!(file, "impl {} {{", t.class_name)?;
+        for (i, (accept, visitor, _, reference)) in variants.iter().enumerate() {
+            if i > 0 {
+                writeln!(file)?;
+            }
+            writeln!(
+                file,
+                "    pub fn {accept}<T>(&self, visitor: {reference} {}{visitor}<T>) -> Result<T, LoxError> {{",
+                base_name
+            )?;
+            writeln!(
+                file,
+                "        visitor.visit_{}_{}(self)",
+                t.base_class_name.to_lowercase(),
+                base_name.to_lowercase()
+            )?;
+            writeln!(file, "    }}")?;
+        }
+        writeln!(file, "}}\n")?;
     }
 
     Ok(())
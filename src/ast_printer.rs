@@ -1,5 +1,6 @@
 use crate::error::*;
 use crate::expr::*;
+use crate::stmt::*;
 
 pub struct AstPrinter;
 
@@ -11,7 +12,11 @@ impl AstPrinter {
         expr.accept(self)
     }
 
-    fn parenthesize(&self, name: &String, exprs: &[&Box<Expr>]) -> Result<String, LoxError> {
+    pub fn print_stmt(&self, stmt: &Stmt) -> Result<String, LoxError> {
+        stmt.accept(self)
+    }
+
+    fn parenthesize(&self, name: &str, exprs: &[&Expr]) -> Result<String, LoxError> {
         let mut builder = format!("({name}");
 
         for expr in exprs {
@@ -21,15 +26,44 @@ impl AstPrinter {
 
         Ok(builder)
     }
+
+    /// Shows a declaration's doc comment, with a leading space, if it has one.
+    fn doc(doc: &Option<String>) -> String {
+        match doc {
+            Some(doc) => format!(" (doc {doc:?})"),
+            None => String::new(),
+        }
+    }
 }
 
 impl ExprVisitor<String> for AstPrinter {
+    fn visit_assign_expr(&self, expr: &AssignExpr) -> Result<String, LoxError> {
+        self.parenthesize(&format!("= {}", expr.name.get_lexeme()), &[&expr.value])
+    }
+
     fn visit_binary_expr(&self, expr: &BinaryExpr) -> Result<String, LoxError> {
         self.parenthesize(&expr.operator.to_string(), &[&expr.left, &expr.right])
     }
 
+    fn visit_call_expr(&self, expr: &CallExpr) -> Result<String, LoxError> {
+        let mut builder = format!("(call {}", self.print(&expr.callee)?);
+        for argument in &expr.arguments {
+            builder = format!("{builder} {}", self.print(argument)?);
+        }
+        Ok(format!("{builder})"))
+    }
+
+    fn visit_get_expr(&self, expr: &GetExpr) -> Result<String, LoxError> {
+        Ok(format!("(. {} {})", self.print(&expr.object)?, expr.name.get_lexeme()))
+    }
+
     fn visit_grouping_expr(&self, expr: &GroupingExpr) -> Result<String, LoxError> {
-        self.parenthesize(&"group".to_string(), &[&expr.expression])
+        self.parenthesize("group", &[&expr.expression])
+    }
+
+    fn visit_interpolation_expr(&self, expr: &InterpolationExpr) -> Result<String, LoxError> {
+        let parts: Vec<&Expr> = expr.parts.iter().collect();
+        self.parenthesize("interpolate", &parts)
     }
 
     fn visit_literal_expr(&self, expr: &LiteralExpr) -> Result<String, LoxError> {
@@ -40,7 +74,115 @@ impl ExprVisitor<String> for AstPrinter {
         }
     }
 
+    fn visit_logical_expr(&self, expr: &LogicalExpr) -> Result<String, LoxError> {
+        self.parenthesize(expr.operator.get_lexeme(), &[&expr.left, &expr.right])
+    }
+
+    fn visit_set_expr(&self, expr: &SetExpr) -> Result<String, LoxError> {
+        Ok(format!(
+            "(= {} {} {})",
+            self.print(&expr.object)?,
+            expr.name.get_lexeme(),
+            self.print(&expr.value)?
+        ))
+    }
+
+    fn visit_super_expr(&self, expr: &SuperExpr) -> Result<String, LoxError> {
+        Ok(format!("(super {})", expr.method.get_lexeme()))
+    }
+
+    fn visit_this_expr(&self, _expr: &ThisExpr) -> Result<String, LoxError> {
+        Ok("this".to_string())
+    }
+
     fn visit_unary_expr(&self, expr: &UnaryExpr) -> Result<String, LoxError> {
         self.parenthesize(&expr.operator.to_string(), &[&expr.right])
     }
+
+    fn visit_variable_expr(&self, expr: &VariableExpr) -> Result<String, LoxError> {
+        Ok(expr.name.get_lexeme().to_string())
+    }
+}
+
+impl StmtVisitor<String> for AstPrinter {
+    fn visit_block_stmt(&self, stmt: &BlockStmt) -> Result<String, LoxError> {
+        let mut builder = "(block".to_string();
+        for statement in &stmt.statements {
+            builder = format!("{builder} {}", statement.accept(self)?);
+        }
+        Ok(format!("{builder})"))
+    }
+
+    fn visit_class_stmt(&self, stmt: &ClassStmt) -> Result<String, LoxError> {
+        let mut builder = format!("(class {}", stmt.name.get_lexeme());
+        if let Some(superclass) = &stmt.superclass {
+            builder = format!("{builder} < {}", self.print(superclass)?);
+        }
+        builder.push_str(&Self::doc(&stmt.doc));
+        for method in &stmt.methods {
+            builder = format!("{builder} {}", self.visit_function_stmt(method)?);
+        }
+        Ok(format!("{builder})"))
+    }
+
+    fn visit_expression_stmt(&self, stmt: &ExpressionStmt) -> Result<String, LoxError> {
+        Ok(format!("(; {})", self.print(&stmt.expression)?))
+    }
+
+    fn visit_function_stmt(&self, stmt: &FunctionStmt) -> Result<String, LoxError> {
+        let params: Vec<String> = stmt.params.iter().map(|p| p.get_lexeme().to_string()).collect();
+        let mut builder = format!(
+            "(fun {} ({}){}",
+            stmt.name.get_lexeme(),
+            params.join(" "),
+            Self::doc(&stmt.doc)
+        );
+        for statement in stmt.body.iter() {
+            builder = format!("{builder} {}", statement.accept(self)?);
+        }
+        Ok(format!("{builder})"))
+    }
+
+    fn visit_if_stmt(&self, stmt: &IfStmt) -> Result<String, LoxError> {
+        let condition = self.print(&stmt.condition)?;
+        let then_branch = stmt.then_branch.accept(self)?;
+        match &stmt.else_branch {
+            Some(else_branch) => Ok(format!(
+                "(if {condition} {then_branch} {})",
+                else_branch.accept(self)?
+            )),
+            None => Ok(format!("(if {condition} {then_branch})")),
+        }
+    }
+
+    fn visit_print_stmt(&self, stmt: &PrintStmt) -> Result<String, LoxError> {
+        Ok(format!("(print {})", self.print(&stmt.expression)?))
+    }
+
+    fn visit_return_stmt(&self, stmt: &ReturnStmt) -> Result<String, LoxError> {
+        match &stmt.value {
+            Some(value) => Ok(format!("(return {})", self.print(value)?)),
+            None => Ok("(return)".to_string()),
+        }
+    }
+
+    fn visit_var_stmt(&self, stmt: &VarStmt) -> Result<String, LoxError> {
+        let doc = Self::doc(&stmt.doc);
+        match &stmt.initializer {
+            Some(initializer) => Ok(format!(
+                "(var {}{doc} {})",
+                stmt.name.get_lexeme(),
+                self.print(initializer)?
+            )),
+            None => Ok(format!("(var {}{doc})", stmt.name.get_lexeme())),
+        }
+    }
+
+    fn visit_while_stmt(&self, stmt: &WhileStmt) -> Result<String, LoxError> {
+        Ok(format!(
+            "(while {} {})",
+            self.print(&stmt.condition)?,
+            stmt.body.accept(self)?
+        ))
+    }
 }
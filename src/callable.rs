@@ -0,0 +1,6 @@
+use crate::{error::LoxError, interpreter::Interpreter, value::Value};
+
+pub trait LoxCallable {
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, LoxError>;
+    fn arity(&self) -> usize;
+}
@@ -0,0 +1,597 @@
+use std::{fmt, rc::Rc};
+
+use crate::{
+    error::LoxError,
+    expr::Expr,
+    parser::Parser,
+    stmt::Stmt,
+    token::{Span, Token, TokenType},
+};
+
+// A lossless syntax tree in the red/green style. Green nodes hold only kinds,
+// text and widths, so a subtree doesn't know where it is and can be shared or
+// rebuilt cheaply. Red nodes (`SyntaxNode`, `SyntaxToken`) are created while
+// walking down from the root and know their absolute position in the source.
+// Every byte of the source is in exactly one token, including whitespace,
+// comments and text the scanner rejected, so printing the tree reproduces the
+// source exactly.
+
+/// What a node of the syntax tree is. Statement and expression nodes are named
+/// after the `Stmt` and `Expr` variants they correspond to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SyntaxKind {
+    Program,
+    /// A declaration that failed to parse, with the tokens skipped to recover.
+    Error,
+
+    // Statements. `Function` is both a `fun` declaration and a method.
+    Block,
+    Class,
+    Expression,
+    For,
+    Function,
+    If,
+    Print,
+    Return,
+    Var,
+    While,
+
+    // Expressions.
+    Assign,
+    Binary,
+    Call,
+    Get,
+    Grouping,
+    Interpolation,
+    Literal,
+    Logical,
+    Set,
+    Super,
+    This,
+    Unary,
+    Variable,
+}
+
+#[derive(Debug)]
+pub struct GreenToken {
+    kind: TokenType,
+    text: String,
+}
+
+#[derive(Debug)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    width: usize,
+    children: Vec<GreenElement>,
+}
+
+#[derive(Clone, Debug)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    fn width(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.width,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+}
+
+impl GreenNode {
+    fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        let width = children.iter().map(GreenElement::width).sum();
+        Self {
+            kind,
+            width,
+            children,
+        }
+    }
+}
+
+/// Marks a position in the builder that a node can later be started from.
+#[derive(Copy, Clone, Default)]
+pub struct Checkpoint(usize);
+
+/// Builds a green tree from the tokens a parser consumes. A node is made by
+/// taking a checkpoint before parsing it and wrapping everything added since
+/// once it has parsed, so nodes are never left open when parsing fails part of
+/// the way through.
+pub struct SyntaxBuilder {
+    source: String,
+    // Elements not yet wrapped in a node.
+    children: Vec<GreenElement>,
+    // Trivia before the next token, added along with it.
+    trivia: Vec<Token>,
+    // Bytes of the source covered so far.
+    offset: usize,
+}
+
+impl SyntaxBuilder {
+    pub fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            children: Vec::new(),
+            trivia: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    /// Marks where a node whose first token starts at `start` begins. The
+    /// trivia before that token is added first, so that it goes outside the
+    /// node and the node's span is that of its tokens.
+    pub fn checkpoint(&mut self, start: usize) -> Checkpoint {
+        for trivia in std::mem::take(&mut self.trivia) {
+            self.push(&trivia);
+        }
+        self.fill_to(start);
+        Checkpoint(self.children.len())
+    }
+
+    /// Wraps everything added since `checkpoint` in a node of the given kind.
+    pub fn wrap(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        let children = self.children.split_off(checkpoint.0);
+        self.children
+            .push(GreenElement::Node(Rc::new(GreenNode::new(kind, children))));
+    }
+
+    /// Queues whitespace or a comment to go in front of the next token.
+    pub fn trivia(&mut self, token: Token) {
+        self.trivia.push(token);
+    }
+
+    /// Adds a token the parser consumed, after any trivia before it.
+    pub fn token(&mut self, token: &Token) {
+        for trivia in std::mem::take(&mut self.trivia) {
+            self.push(&trivia);
+        }
+        self.push(token);
+    }
+
+    pub fn finish(mut self) -> GreenNode {
+        for trivia in std::mem::take(&mut self.trivia) {
+            self.push(&trivia);
+        }
+        self.fill_to(self.source.len());
+        GreenNode::new(SyntaxKind::Program, self.children)
+    }
+
+    fn push(&mut self, token: &Token) {
+        let span = token.get_span();
+        self.fill_to(span.start);
+        self.children.push(GreenElement::Token(Rc::new(GreenToken {
+            kind: token.get_token_type(),
+            text: self.source[span.start..span.end].to_string(),
+        })));
+        self.offset = span.end;
+    }
+
+    /// Covers source text that no token was made from, such as an unexpected
+    /// character, with an Error token.
+    fn fill_to(&mut self, end: usize) {
+        if end > self.offset {
+            self.children.push(GreenElement::Token(Rc::new(GreenToken {
+                kind: TokenType::Error,
+                text: self.source[self.offset..end].to_string(),
+            })));
+            self.offset = end;
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SyntaxNode {
+    green: Rc<GreenNode>,
+    offset: usize,
+}
+
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    offset: usize,
+}
+
+#[derive(Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    fn new_root(green: GreenNode) -> Self {
+        Self {
+            green: Rc::new(green),
+            offset: 0,
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    /// The source this node covers, including trivia inside it.
+    pub fn span(&self) -> Span {
+        Span::new(self.offset, self.offset + self.green.width)
+    }
+
+    pub fn children(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.offset;
+        self.green
+            .children
+            .iter()
+            .map(|child| {
+                let element = match child {
+                    GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode {
+                        green: Rc::clone(green),
+                        offset,
+                    }),
+                    GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                        green: Rc::clone(green),
+                        offset,
+                    }),
+                };
+                offset += child.width();
+                element
+            })
+            .collect()
+    }
+
+    /// This node and every node below it, parents before their children.
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut nodes = vec![self.clone()];
+        for child in self.children() {
+            if let SyntaxElement::Node(node) = child {
+                nodes.extend(node.descendants());
+            }
+        }
+        nodes
+    }
+
+    /// Shows the tree one element per line, indented by depth, for debugging.
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        self.dump_into(&mut out, 0);
+        out
+    }
+
+    fn dump_into(&self, out: &mut String, depth: usize) {
+        let span = self.span();
+        out.push_str(&format!(
+            "{:indent$}{:?}@{}..{}\n",
+            "",
+            self.kind(),
+            span.start,
+            span.end,
+            indent = depth * 2
+        ));
+        for child in self.children() {
+            match child {
+                SyntaxElement::Node(node) => node.dump_into(out, depth + 1),
+                SyntaxElement::Token(token) => {
+                    let span = token.span();
+                    out.push_str(&format!(
+                        "{:indent$}{:?}@{}..{} {:?}\n",
+                        "",
+                        token.kind(),
+                        span.start,
+                        span.end,
+                        token.text(),
+                        indent = (depth + 1) * 2
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Prints the exact source text the node was parsed from.
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_green(f: &mut fmt::Formatter<'_>, node: &GreenNode) -> fmt::Result {
+            for child in &node.children {
+                match child {
+                    GreenElement::Node(node) => write_green(f, node)?,
+                    GreenElement::Token(token) => f.write_str(&token.text)?,
+                }
+            }
+            Ok(())
+        }
+        write_green(f, &self.green)
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> TokenType {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.offset, self.offset + self.green.text.len())
+    }
+}
+
+/// A program parsed both into a lossless syntax tree and into the `Stmt` and
+/// `Expr` nodes the resolver and interpreter work with.
+pub struct SyntaxTree {
+    pub root: SyntaxNode,
+    pub statements: Vec<Stmt>,
+}
+
+impl SyntaxTree {
+    pub fn parse(source: &str) -> (SyntaxTree, Vec<LoxError>) {
+        let mut parser = Parser::new_lossless(source);
+        let (statements, errors) = parser.parse();
+        let root = SyntaxNode::new_root(parser.finish_syntax().unwrap());
+        (SyntaxTree { root, statements }, errors)
+    }
+
+    /// Pairs each top-level declaration's syntax node with its statement.
+    /// Declarations that failed to parse have an Error node and no statement,
+    /// and are left out.
+    pub fn declarations(&self) -> impl Iterator<Item = (SyntaxNode, &Stmt)> {
+        self.root
+            .children()
+            .into_iter()
+            .filter_map(|child| match child {
+                SyntaxElement::Node(node) if node.kind() != SyntaxKind::Error => Some(node),
+                _ => None,
+            })
+            .zip(&self.statements)
+    }
+
+    /// The expression parsed from `node`, which is the one with the node's
+    /// kind and span. Nodes that aren't expressions have none, and neither do
+    /// assignment targets: the `Variable` or `Get` node on the left of `=` is
+    /// parsed into just the name of an `Assign` or `Set`.
+    pub fn expr(&self, node: &SyntaxNode) -> Option<&Expr> {
+        fn find<'e>(expr: &'e Expr, node: &SyntaxNode) -> Option<&'e Expr> {
+            if expr.span() == node.span() && expr_kind(expr) == node.kind() {
+                return Some(expr);
+            }
+            let children: Vec<&Expr> = match expr {
+                Expr::Assign(e) => vec![&e.value],
+                Expr::Binary(e) => vec![&e.left, &e.right],
+                Expr::Call(e) => std::iter::once(&*e.callee).chain(&e.arguments).collect(),
+                Expr::Get(e) => vec![&e.object],
+                Expr::Grouping(e) => vec![&e.expression],
+                Expr::Interpolation(e) => e.parts.iter().collect(),
+                Expr::Logical(e) => vec![&e.left, &e.right],
+                Expr::Set(e) => vec![&e.object, &e.value],
+                Expr::Unary(e) => vec![&e.right],
+                Expr::Literal(_) | Expr::Super(_) | Expr::This(_) | Expr::Variable(_) => vec![],
+            };
+            children.into_iter().find_map(|child| find(child, node))
+        }
+
+        fn find_in<'s>(statement: &'s Stmt, node: &SyntaxNode) -> Option<&'s Expr> {
+            let (exprs, statements): (Vec<&Expr>, Vec<&Stmt>) = match statement {
+                Stmt::Block(s) => (vec![], s.statements.iter().collect()),
+                Stmt::Class(s) => (
+                    s.superclass.iter().map(|e| &**e).collect(),
+                    s.methods.iter().flat_map(|m| m.body.iter()).collect(),
+                ),
+                Stmt::Expression(s) => (vec![&s.expression], vec![]),
+                Stmt::Function(s) => (vec![], s.body.iter().collect()),
+                Stmt::If(s) => (
+                    vec![&s.condition],
+                    std::iter::once(&*s.then_branch)
+                        .chain(s.else_branch.as_deref())
+                        .collect(),
+                ),
+                Stmt::Print(s) => (vec![&s.expression], vec![]),
+                Stmt::Return(s) => (s.value.iter().map(|e| &**e).collect(), vec![]),
+                Stmt::Var(s) => (s.initializer.iter().map(|e| &**e).collect(), vec![]),
+                Stmt::While(s) => (vec![&s.condition], vec![&s.body]),
+            };
+            exprs
+                .into_iter()
+                .find_map(|expr| find(expr, node))
+                .or_else(|| statements.into_iter().find_map(|s| find_in(s, node)))
+        }
+
+        self.statements
+            .iter()
+            .find_map(|statement| find_in(statement, node))
+    }
+}
+
+fn expr_kind(expr: &Expr) -> SyntaxKind {
+    match expr {
+        Expr::Assign(_) => SyntaxKind::Assign,
+        Expr::Binary(_) => SyntaxKind::Binary,
+        Expr::Call(_) => SyntaxKind::Call,
+        Expr::Get(_) => SyntaxKind::Get,
+        Expr::Grouping(_) => SyntaxKind::Grouping,
+        Expr::Interpolation(_) => SyntaxKind::Interpolation,
+        Expr::Literal(_) => SyntaxKind::Literal,
+        Expr::Logical(_) => SyntaxKind::Logical,
+        Expr::Set(_) => SyntaxKind::Set,
+        Expr::Super(_) => SyntaxKind::Super,
+        Expr::This(_) => SyntaxKind::This,
+        Expr::Unary(_) => SyntaxKind::Unary,
+        Expr::Variable(_) => SyntaxKind::Variable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast_printer::AstPrinter, scanner::Scanner};
+
+    const TRICKY: &str =
+        "/// Docs.\r\nclass A < B { /* nested /* comment */ */\n  init(x) { this.x = x; } }\n\
+        var s = \"tab\\t ${1 + \"in${2}\"} é\";\tprint -0x1F_FF * (2.5e3);\n\
+        fun f(a, b) { return a.b(c)[0]; } @ print 1_; var t = \"bad \\q\";\n\
+        for (var i = 0; i < 3; i = i + 1) if (i) print i; else { print nil; } // end";
+
+    fn assert_round_trip(source: &str) {
+        let (tree, _) = SyntaxTree::parse(source);
+        assert_eq!(tree.root.to_string(), source);
+        assert_eq!(tree.root.span(), Span::new(0, source.len()));
+    }
+
+    #[test]
+    fn test_round_trips_examples() {
+        for entry in std::fs::read_dir("examples").unwrap() {
+            let source = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            assert_round_trip(&source);
+        }
+    }
+
+    #[test]
+    fn test_round_trips_every_prefix() {
+        // Cutting the source off anywhere leaves unterminated strings, comments and
+        // declarations, which must all still be in the tree.
+        for (end, _) in TRICKY.char_indices() {
+            assert_round_trip(&TRICKY[..end]);
+        }
+        assert_round_trip(TRICKY);
+        assert_round_trip("/* open");
+        assert_round_trip("print \"open ${1");
+    }
+
+    #[test]
+    fn test_same_statements_and_errors_as_parser() {
+        let printer = AstPrinter::new();
+        let print = |statements: &[Stmt]| -> Vec<String> {
+            statements
+                .iter()
+                .map(|s| printer.print_stmt(s).unwrap())
+                .collect()
+        };
+        let (tree, errors) = SyntaxTree::parse(TRICKY);
+        let (statements, expected_errors) = Parser::new(Scanner::new(TRICKY)).parse();
+        assert_eq!(print(&tree.statements), print(&statements));
+        let messages =
+            |errors: &[LoxError]| -> Vec<String> { errors.iter().map(|e| e.to_string()).collect() };
+        assert_eq!(messages(&errors), messages(&expected_errors));
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn test_tree_structure() {
+        let (tree, errors) = SyntaxTree::parse("a.b = -c; // done\n");
+        assert!(errors.is_empty());
+        let expected = "\
+Program@0..18
+  Expression@0..9
+    Set@0..8
+      Get@0..3
+        Variable@0..1
+          Identifier@0..1 \"a\"
+        Dot@1..2 \".\"
+        Identifier@2..3 \"b\"
+      Whitespace@3..4 \" \"
+      Equal@4..5 \"=\"
+      Whitespace@5..6 \" \"
+      Unary@6..8
+        Minus@6..7 \"-\"
+        Variable@7..8
+          Identifier@7..8 \"c\"
+    Semicolon@8..9 \";\"
+  Whitespace@9..10 \" \"
+  Comment@10..17 \"// done\"
+  Whitespace@17..18 \"\\n\"
+";
+        assert_eq!(tree.root.dump(), expected);
+    }
+
+    #[test]
+    fn test_expression_nodes_have_exprs() {
+        let source = "class A < B { m(x) { return super.m(-x) + this.y; } }\n\
+            fun f(a) { for (var i = 0; i < a; i = i + 1) { print \"${i * 2}!\"; } }\n\
+            var o = A(); o.p = (1 + 2) * f(3) or !nil;";
+        let (tree, errors) = SyntaxTree::parse(source);
+        assert!(errors.is_empty());
+        let printer = AstPrinter::new();
+        let mut found = 0;
+        for node in tree.root.descendants() {
+            let span = node.span();
+            // Nodes begin and end at tokens, like the expressions they are for.
+            assert!(!source[span.start..span.end].starts_with(char::is_whitespace));
+            let is_statement = matches!(
+                node.kind(),
+                SyntaxKind::Program
+                    | SyntaxKind::Block
+                    | SyntaxKind::Class
+                    | SyntaxKind::Expression
+                    | SyntaxKind::For
+                    | SyntaxKind::Function
+                    | SyntaxKind::If
+                    | SyntaxKind::Print
+                    | SyntaxKind::Return
+                    | SyntaxKind::Var
+                    | SyntaxKind::While
+            );
+            let is_assignment_target =
+                matches!(node.kind(), SyntaxKind::Get | SyntaxKind::Variable)
+                    && source[span.end..].trim_start().starts_with("= ");
+            match tree.expr(&node) {
+                Some(expr) => {
+                    assert_eq!(expr.span(), span);
+                    found += 1;
+                }
+                None => assert!(
+                    is_statement || is_assignment_target,
+                    "{:?}@{}..{}",
+                    node.kind(),
+                    span.start,
+                    span.end
+                ),
+            }
+        }
+        assert!(found > 20);
+
+        let node = tree
+            .root
+            .descendants()
+            .into_iter()
+            .find(|node| node.kind() == SyntaxKind::Set)
+            .unwrap();
+        assert_eq!(node.to_string(), "o.p = (1 + 2) * f(3) or !nil");
+        let expr = tree.expr(&node).unwrap();
+        assert!(printer.print(expr).unwrap().starts_with("(= o p "));
+    }
+
+    #[test]
+    fn test_declarations_skip_errors() {
+        let (tree, errors) = SyntaxTree::parse("var a = 1;\nvar = 2;\nprint a;");
+        assert_eq!(errors.len(), 1);
+        let kinds: Vec<SyntaxKind> = tree
+            .root
+            .children()
+            .into_iter()
+            .filter_map(|child| match child {
+                SyntaxElement::Node(node) => Some(node.kind()),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [SyntaxKind::Var, SyntaxKind::Error, SyntaxKind::Print]
+        );
+
+        let declarations: Vec<(SyntaxKind, String)> = tree
+            .declarations()
+            .map(|(node, statement)| {
+                (
+                    node.kind(),
+                    AstPrinter::new().print_stmt(statement).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            declarations,
+            [
+                (SyntaxKind::Var, "(var a 1)".to_string()),
+                (SyntaxKind::Print, "(print a)".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+    error::{Annotation, LoxError},
+    token::Span,
+    unicode,
+};
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+const TAB_WIDTH: usize = 4;
+
+/// How errors are printed: for people reading a terminal, or as one JSON
+/// object per line for tools.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+impl std::str::FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("Unknown error format '{s}'. Expected 'human' or 'json'.")),
+        }
+    }
+}
+
+/// Renders errors against the source they came from, pointing at the
+/// offending code:
+///
+/// ```text
+/// error[L0017]: Operands must be numbers.
+///  --> script.lox:1:9
+///   |
+/// 1 | print 1 - "b";
+///   |       - ^ --- this is a string
+///   |       |
+///   |       this is a number
+/// ```
+pub struct Renderer<'a> {
+    source: &'a str,
+    origin: &'a str,
+    color: bool,
+}
+
+/// One underlined span within a single source line.
+struct Mark {
+    column: usize,
+    width: usize,
+    primary: bool,
+    label: String,
+}
+
+impl<'a> Renderer<'a> {
+    /// `origin` names the source in the location line, usually the script's path.
+    pub fn new(source: &'a str, origin: &'a str, color: bool) -> Self {
+        Self {
+            source,
+            origin,
+            color,
+        }
+    }
+
+    pub fn render(&self, error: &LoxError) -> String {
+        let mut out = String::new();
+        self.write(&mut out, error)
+            .expect("writing to a String can't fail");
+        out
+    }
+
+    /// Renders an error as a single-line JSON object:
+    ///
+    /// ```text
+    /// {"file":"a.lox","line":1,"column":7,"span":{"start":6,"end":8},"severity":"error","code":"L0015","message":"..."}
+    /// ```
+    ///
+    /// `span` is null when the error has no exact location.
+    pub fn render_json(&self, error: &LoxError) -> String {
+        let span = match error.span {
+            Some(span) => format!("{{\"start\":{},\"end\":{}}}", span.start, span.end),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"file\":{},\"line\":{},\"column\":{},\"span\":{span},\"severity\":\"error\",\"code\":\"{}\",\"message\":{}}}",
+            json_string(self.origin),
+            error.line,
+            error.column,
+            error.code,
+            json_string(&error.message)
+        )
+    }
+
+    fn write(&self, out: &mut String, error: &LoxError) -> std::fmt::Result {
+        let mut lines: BTreeMap<usize, Vec<Mark>> = BTreeMap::new();
+        let mut location = (error.line > 0).then(|| error.line.to_string());
+        if let Some(span) = error.span {
+            let label = error.annotations.iter().find_map(|a| match a {
+                Annotation::Label(label) => Some(label.clone()),
+                _ => None,
+            });
+            let (line, column, mark) = self.mark(span, true, label.unwrap_or_default());
+            location = Some(format!("{line}:{column}"));
+            lines.entry(line).or_default().push(mark);
+
+            for annotation in &error.annotations {
+                if let Annotation::Secondary(span, label) = annotation {
+                    let (line, _, mark) = self.mark(*span, false, label.clone());
+                    lines.entry(line).or_default().push(mark);
+                }
+            }
+        }
+
+        let width = lines
+            .keys()
+            .last()
+            .map_or(1, |line| line.to_string().len());
+        let gutter = |line: &str| self.paint(BLUE, &format!("{line:>width$} |"));
+
+        writeln!(
+            out,
+            "{}{}",
+            self.paint(RED, &format!("error[{}]", error.code)),
+            self.paint(BOLD, &format!(": {}", error.message))
+        )?;
+        if let Some(location) = location {
+            writeln!(out, "{:width$}{} {}:{location}", "", self.paint(BLUE, "-->"), self.origin)?;
+        }
+        if !lines.is_empty() {
+            writeln!(out, "{}", gutter(""))?;
+        }
+
+        let mut previous = None;
+        for (line, mut marks) in lines {
+            if previous.is_some_and(|p: usize| line > p + 1) {
+                writeln!(out, "{}", self.paint(BLUE, "..."))?;
+            }
+            previous = Some(line);
+
+            let text = expand_tabs(self.line_text(line));
+            writeln!(out, "{} {}", gutter(&line.to_string()), text.trim_end())?;
+
+            marks.sort_by_key(|mark| mark.column);
+            self.write_marks(out, &gutter(""), &marks)?;
+        }
+
+        let notes = error.annotations.iter().filter_map(|a| match a {
+            Annotation::Note(note) => Some(("note", note)),
+            Annotation::Help(help) => Some(("help", help)),
+            _ => None,
+        });
+        for (kind, text) in notes {
+            writeln!(
+                out,
+                "{:width$} {} {}: {text}",
+                "",
+                self.paint(BLUE, "="),
+                self.paint(BOLD, kind)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the underline row for one source line, followed by a row per
+    /// label that doesn't fit inline after the rightmost underline.
+    fn write_marks(&self, out: &mut String, gutter: &str, marks: &[Mark]) -> std::fmt::Result {
+        let style = |mark: &Mark| if mark.primary { RED } else { BLUE };
+
+        let mut row = Vec::new();
+        for mark in marks {
+            let underline = if mark.primary { "^" } else { "-" }.repeat(mark.width);
+            row.push((mark.column, underline, style(mark)));
+        }
+        let (last, rest) = marks.split_last().unwrap();
+        if !last.label.is_empty() {
+            row.push((last.column + last.width + 1, last.label.clone(), style(last)));
+        }
+        writeln!(out, "{gutter} {}", self.row(&row))?;
+
+        let hanging: Vec<&Mark> = rest.iter().filter(|mark| !mark.label.is_empty()).collect();
+        if hanging.is_empty() {
+            return Ok(());
+        }
+        let pipes = |marks: &[&Mark]| -> Vec<(usize, String, &str)> {
+            marks
+                .iter()
+                .map(|mark| (mark.column, "|".to_string(), style(mark)))
+                .collect()
+        };
+        writeln!(out, "{gutter} {}", self.row(&pipes(&hanging)))?;
+        for i in (0..hanging.len()).rev() {
+            let mut row = pipes(&hanging[..i]);
+            row.push((hanging[i].column, hanging[i].label.clone(), style(hanging[i])));
+            writeln!(out, "{gutter} {}", self.row(&row))?;
+        }
+        Ok(())
+    }
+
+    /// Lays out `(column, text, style)` cells left to right on one row.
+    fn row(&self, cells: &[(usize, String, &str)]) -> String {
+        let mut row = String::new();
+        let mut cursor = 0;
+        for (column, text, style) in cells {
+            if *column > cursor {
+                row.push_str(&" ".repeat(column - cursor));
+                cursor = *column;
+            }
+            row.push_str(&self.paint(style, text));
+            cursor += display_width(text);
+        }
+        row
+    }
+
+    /// Finds the line and 1-based column a span starts at, and where to underline
+    /// it. Spans running past the end of their first line are cut off there.
+    fn mark(&self, span: Span, primary: bool, label: String) -> (usize, usize, Mark) {
+        let mut start = span.start.min(self.source.len());
+        // Errors at the end of input would otherwise point past trailing newlines.
+        if span.start == span.end && start == self.source.len() {
+            start = self.source.trim_end().len();
+        }
+        let end = span.end.clamp(start, self.source.len());
+
+        let line_start = self.source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.source[start..]
+            .find('\n')
+            .map_or(self.source.len(), |i| start + i);
+        let line = self.source[..start].matches('\n').count() + 1;
+
+        let prefix = &self.source[line_start..start];
+        let column = display_width(prefix);
+        let width = display_width(&self.source[start..end.min(line_end)]).max(1);
+        (
+            line,
+            prefix.chars().count() + 1,
+            Mark {
+                column,
+                width,
+                primary,
+                label,
+            },
+        )
+    }
+
+    fn line_text(&self, line: usize) -> &str {
+        self.source.lines().nth(line - 1).unwrap_or("")
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| if c == '\t' { TAB_WIDTH } else { unicode::display_width(c) })
+        .sum()
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+/// Quotes and escapes `text` as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                write!(out, "\\u{:04x}", c as u32).expect("writing to a String can't fail")
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_code::ErrorCode;
+
+    fn render(source: &str, error: &LoxError) -> String {
+        Renderer::new(source, "test.lox", false).render(error)
+    }
+
+    #[test]
+    fn test_underlines_span() {
+        let source = "var a = 1;\nprint a +  nil;\n";
+        let error = LoxError::new_with_span(ErrorCode::OperandType, 2, 9, Span::new(19, 20), "Bad.".to_string())
+            .with_label("here");
+        assert_eq!(
+            render(source, &error),
+            "error[L0017]: Bad.\n --> test.lox:2:9\n  |\n2 | print a +  nil;\n  |         ^ here\n"
+        );
+    }
+
+    #[test]
+    fn test_secondary_labels_notes_and_help() {
+        let source = "print 1 - \"b\";";
+        let error = LoxError::new_with_span(
+            ErrorCode::OperandType,
+            1,
+            9,
+            Span::new(8, 9),
+            "Operands must be numbers.".to_string(),
+        )
+            .with_secondary(Span::new(6, 7), "this is a number")
+            .with_secondary(Span::new(10, 13), "this is a string")
+            .with_note("a note")
+            .with_help("some help");
+        let expected = "\
+error[L0017]: Operands must be numbers.
+ --> test.lox:1:9
+  |
+1 | print 1 - \"b\";
+  |       - ^ --- this is a string
+  |       |
+  |       this is a number
+  = note: a note
+  = help: some help
+";
+        assert_eq!(render(source, &error), expected);
+    }
+
+    #[test]
+    fn test_wide_and_combining_characters() {
+        // Two wide CJK characters and a decomposed "é" before the error.
+        let source = "print \"世界\" + cafe\u{301} - nil;";
+        let error = LoxError::new_with_span(
+            ErrorCode::OperandType,
+            1,
+            20,
+            Span::new(24, 25),
+            "Operands must be numbers.".to_string(),
+        );
+        let rendered = render(source, &error);
+        assert!(rendered.contains(" --> test.lox:1:20\n"));
+        assert!(rendered.ends_with(&format!("  | {}^\n", " ".repeat(20))));
+    }
+
+    #[test]
+    fn test_error_at_end_of_input() {
+        let source = "print 1\n\n";
+        let error = LoxError::new_with_span(
+            ErrorCode::ExpectedToken,
+            3,
+            1,
+            Span::new(9, 9),
+            "Expect ';' after value.".to_string(),
+        );
+        assert!(render(source, &error).ends_with("1 | print 1\n  |        ^\n"));
+    }
+
+    #[test]
+    fn test_json() {
+        let error = LoxError::new_with_span(
+            ErrorCode::UndefinedVariable,
+            2,
+            7,
+            Span::new(17, 19),
+            "Undefined variable 'yy'.".to_string(),
+        );
+        assert_eq!(
+            Renderer::new("", "dir/a \"b\".lox", false).render_json(&error),
+            r#"{"file":"dir/a \"b\".lox","line":2,"column":7,"span":{"start":17,"end":19},"severity":"error","code":"L0015","message":"Undefined variable 'yy'."}"#
+        );
+
+        let error = LoxError::new(ErrorCode::NativeFunction, 3, "".to_string(), "Tab\there\u{1}".to_string());
+        assert_eq!(
+            Renderer::new("", "a.lox", false).render_json(&error),
+            r#"{"file":"a.lox","line":3,"column":0,"span":null,"severity":"error","code":"L0022","message":"Tab\there\u0001"}"#
+        );
+    }
+
+    #[test]
+    fn test_color() {
+        let error = LoxError::new_with_span(
+            ErrorCode::UnexpectedCharacter,
+            1,
+            1,
+            Span::new(0, 1),
+            "Bad.".to_string(),
+        );
+        let colored = Renderer::new("x", "test.lox", true).render(&error);
+        assert!(colored.starts_with("\x1b[1;31merror[L0001]\x1b[0m"));
+        assert!(colored.contains("\x1b[1;31m^\x1b[0m"));
+    }
+}
@@ -0,0 +1,142 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{error::LoxError, error_code::ErrorCode, token::Token, value::Value};
+
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: None,
+        }
+    }
+
+    pub fn new_with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, LoxError> {
+        if let Some(value) = self.values.get(name.get_lexeme()) {
+            return Ok(value.clone());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(Environment::undefined(name)),
+        }
+    }
+
+    /// Reads `name` from the scope exactly `distance` hops up the chain.
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value, LoxError> {
+        if distance == 0 {
+            return self
+                .values
+                .get(name.get_lexeme())
+                .cloned()
+                .ok_or_else(|| Environment::undefined(name));
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get_at(distance - 1, name),
+            None => Err(Environment::undefined(name)),
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), LoxError> {
+        if let Some(slot) = self.values.get_mut(name.get_lexeme()) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(Environment::undefined(name)),
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Value) -> Result<(), LoxError> {
+        if distance == 0 {
+            return match self.values.get_mut(name.get_lexeme()) {
+                Some(slot) => {
+                    *slot = value;
+                    Ok(())
+                }
+                None => Err(Environment::undefined(name)),
+            };
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            None => Err(Environment::undefined(name)),
+        }
+    }
+
+    fn undefined(name: &Token) -> LoxError {
+        LoxError::new_with_token(
+            ErrorCode::UndefinedVariable,
+            name.clone(),
+            format!("Undefined variable '{}'.", name.get_lexeme()),
+        )
+        .with_label("not found in this scope")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::TokenType;
+
+    fn identifier(name: &str) -> Token {
+        Token::new(TokenType::Identifier, name, None, 7)
+    }
+
+    #[test]
+    fn test_get_from_enclosing() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("a", Value::Number(1.0));
+        let mut local = Environment::new_with_enclosing(globals.clone());
+
+        assert_eq!(local.get(&identifier("a")).ok(), Some(Value::Number(1.0)));
+        local.assign(&identifier("a"), Value::Number(2.0)).unwrap();
+        assert_eq!(
+            globals.borrow().get(&identifier("a")).ok(),
+            Some(Value::Number(2.0))
+        );
+    }
+
+    #[test]
+    fn test_get_at_skips_shadowing_scopes() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("a", Value::Number(1.0));
+        let mut local = Environment::new_with_enclosing(globals.clone());
+        local.define("a", Value::Number(2.0));
+
+        assert_eq!(local.get_at(0, &identifier("a")).ok(), Some(Value::Number(2.0)));
+        assert_eq!(local.get_at(1, &identifier("a")).ok(), Some(Value::Number(1.0)));
+        local.assign_at(1, &identifier("a"), Value::Number(3.0)).unwrap();
+        assert_eq!(local.get_at(0, &identifier("a")).ok(), Some(Value::Number(2.0)));
+        assert_eq!(
+            globals.borrow().get(&identifier("a")).ok(),
+            Some(Value::Number(3.0))
+        );
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let env = Environment::new();
+        let err = env.get(&identifier("missing")).err().unwrap();
+        assert_eq!(err.line, 7);
+        assert_eq!(err.message, "Undefined variable 'missing'.");
+    }
+}
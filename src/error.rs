@@ -1,48 +1,92 @@
 use core::fmt;
 
-use crate::token::{Token, TokenType};
+use crate::{
+    error_code::ErrorCode,
+    token::{Span, Token},
+};
+
+/// Extra context shown alongside an error when it is rendered.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Annotation {
+    /// Text printed next to the `^^^` underline of the error's own span.
+    Label(String),
+    /// Another location that helps explain the error, underlined with `---`.
+    Secondary(Span, String),
+    Note(String),
+    Help(String),
+}
 
 #[derive(Clone)]
 pub struct LoxError {
-    pub token: Option<Token>,
+    pub code: ErrorCode,
     pub line: usize,
+    /// 1-based column of the start of `span`, or 0 when the location is unknown.
+    pub column: usize,
+    pub span: Option<Span>,
     pub r#where: String,
     pub message: String,
+    pub annotations: Vec<Annotation>,
 }
 
 impl LoxError {
-    pub fn new(line: usize, r#where: String, message: String) -> Self {
+    pub fn new(code: ErrorCode, line: usize, r#where: String, message: String) -> Self {
         Self {
-            token: None,
+            code,
             line,
+            column: 0,
+            span: None,
             r#where,
             message,
+            annotations: Vec::new(),
         }
     }
 
-    pub fn new_with_token(token: Token, message: String) -> Self {
+    pub fn new_with_span(
+        code: ErrorCode,
+        line: usize,
+        column: usize,
+        span: Span,
+        message: String,
+    ) -> Self {
         Self {
-            token: Some(token.clone()),
-            line: token.get_line(),
+            code,
+            line,
+            column,
+            span: Some(span),
             r#where: "".to_string(),
             message,
+            annotations: Vec::new(),
         }
     }
 
-    pub fn error(token: Token, message: String) {
-        if token.get_token_type() == TokenType::Eof {
-            LoxError::report(token.get_line(), "at end".to_string(), message);
-        } else {
-            LoxError::report(
-                token.get_line(),
-                "at '".to_string() + token.get_lexeme().as_str() + "'",
-                message,
-            )
-        }
+    pub fn new_with_token(code: ErrorCode, token: Token, message: String) -> Self {
+        Self::new_with_span(
+            code,
+            token.get_line(),
+            token.get_column(),
+            token.get_span(),
+            message,
+        )
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.annotations.push(Annotation::Label(label.into()));
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, label: impl Into<String>) -> Self {
+        self.annotations.push(Annotation::Secondary(span, label.into()));
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.annotations.push(Annotation::Note(note.into()));
+        self
     }
 
-    pub fn report(line: usize, r#where: String, message: String) {
-        eprintln!("[line {}] Error {}: {}", line, r#where, message);
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.annotations.push(Annotation::Help(help.into()));
+        self
     }
 }
 
@@ -58,7 +102,15 @@ impl fmt::Debug for LoxError {
 
 impl fmt::Display for LoxError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_fmt(format_args!("[line {}] error: {}", self.line, self.message))
+        if self.column > 0 {
+            write!(
+                f,
+                "[line {}:{}] error[{}]: {}",
+                self.line, self.column, self.code, self.message
+            )
+        } else {
+            write!(f, "[line {}] error[{}]: {}", self.line, self.code, self.message)
+        }
     }
 }
 
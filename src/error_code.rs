@@ -0,0 +1,419 @@
+use core::fmt;
+
+/// Identifies what kind of error a `LoxError` is. Codes are stable: each
+/// variant's number is fixed by its discriminant, new kinds take the next
+/// unused number, and existing ones never change, so documentation and
+/// tooling can refer to them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u16)]
+pub enum ErrorCode {
+    UnexpectedCharacter = 1,
+    UnterminatedString = 2,
+    ExpectedExpression = 3,
+    ExpectedToken = 4,
+    InvalidAssignmentTarget = 5,
+    TooManyArguments = 6,
+    DuplicateDeclaration = 7,
+    ReadInOwnInitializer = 8,
+    TopLevelReturn = 9,
+    ReturnFromInitializer = 10,
+    InvalidThis = 11,
+    InvalidSuper = 12,
+    SuperWithoutSuperclass = 13,
+    InheritFromSelf = 14,
+    UndefinedVariable = 15,
+    UndefinedProperty = 16,
+    OperandType = 17,
+    ArityMismatch = 18,
+    NotCallable = 19,
+    NotAnInstance = 20,
+    SuperclassNotClass = 21,
+    NativeFunction = 22,
+    InvalidEscape = 23,
+    UnterminatedComment = 24,
+    InvalidNumber = 25,
+}
+
+impl ErrorCode {
+    /// Every code.
+    pub const ALL: [ErrorCode; 25] = [
+        ErrorCode::UnexpectedCharacter,
+        ErrorCode::UnterminatedString,
+        ErrorCode::ExpectedExpression,
+        ErrorCode::ExpectedToken,
+        ErrorCode::InvalidAssignmentTarget,
+        ErrorCode::TooManyArguments,
+        ErrorCode::DuplicateDeclaration,
+        ErrorCode::ReadInOwnInitializer,
+        ErrorCode::TopLevelReturn,
+        ErrorCode::ReturnFromInitializer,
+        ErrorCode::InvalidThis,
+        ErrorCode::InvalidSuper,
+        ErrorCode::SuperWithoutSuperclass,
+        ErrorCode::InheritFromSelf,
+        ErrorCode::UndefinedVariable,
+        ErrorCode::UndefinedProperty,
+        ErrorCode::OperandType,
+        ErrorCode::ArityMismatch,
+        ErrorCode::NotCallable,
+        ErrorCode::NotAnInstance,
+        ErrorCode::SuperclassNotClass,
+        ErrorCode::NativeFunction,
+        ErrorCode::InvalidEscape,
+        ErrorCode::UnterminatedComment,
+        ErrorCode::InvalidNumber,
+    ];
+
+    /// Looks up a code such as `L0003`.
+    pub fn from_code(code: &str) -> Option<ErrorCode> {
+        ErrorCode::ALL
+            .into_iter()
+            .find(|c| c.to_string().eq_ignore_ascii_case(code))
+    }
+
+    /// A longer description of the error with an example, as printed by `--explain`.
+    pub fn explain(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedCharacter => {
+                "The scanner found a character that doesn't start any Lox token.
+
+Erroneous example:
+
+    print 1 @ 2;
+
+Lox has no `@` operator. Check for a typo, or for text that was meant to be
+inside a string or a comment."
+            }
+            ErrorCode::UnterminatedString => {
+                "A string literal was opened with `\"` but the file ended before the
+closing quote.
+
+Erroneous example:
+
+    print \"hello;
+
+Add the missing `\"`. Strings may span several lines, so the error points at the
+quote that opened the string. A quote inside a string must be escaped as `\\\"`,
+otherwise it ends the string early."
+            }
+            ErrorCode::ExpectedExpression => {
+                "The parser expected an expression, such as a literal, a variable, a call
+or a parenthesized expression, but found something else.
+
+Erroneous example:
+
+    var a = 1 + ;
+
+Complete the expression:
+
+    var a = 1 + 2;"
+            }
+            ErrorCode::ExpectedToken => {
+                "A piece of required syntax is missing, most often a `;` at the end of a
+statement, a closing `)` or `}`, or a name after `var`, `fun` or `class`.
+
+Erroneous example:
+
+    print \"hi\"
+    print \"there\";
+
+The error points at the token found instead. Add what the message asks for:
+
+    print \"hi\";
+    print \"there\";"
+            }
+            ErrorCode::InvalidAssignmentTarget => {
+                "The left-hand side of `=` is not something that can be assigned to. Only
+variables and fields of instances can be assigned.
+
+Erroneous example:
+
+    var a = 1;
+    a + 1 = 3;
+
+Assign to a variable or a field instead:
+
+    a = 3 - 1;
+    point.x = 3;"
+            }
+            ErrorCode::TooManyArguments => {
+                "A call has more than 255 arguments, or a function declares more than 255
+parameters. Lox limits both to 255.
+
+Pass the values in fewer arguments, for example by grouping them in an instance."
+            }
+            ErrorCode::DuplicateDeclaration => {
+                "A local scope declares the same name twice.
+
+Erroneous example:
+
+    {
+        var a = 1;
+        var a = 2;
+    }
+
+Use a different name, or assign to the existing variable with `a = 2;`.
+Redeclaring a global variable is allowed."
+            }
+            ErrorCode::ReadInOwnInitializer => {
+                "A local variable is used in the expression that initializes it.
+
+Erroneous example:
+
+    var a = 1;
+    {
+        var a = a + 1;
+    }
+
+The inner `a` isn't defined until its initializer finishes. Use a different name
+for the new variable."
+            }
+            ErrorCode::TopLevelReturn => {
+                "A `return` statement appears outside of any function or method.
+
+Erroneous example:
+
+    return 1;
+
+Only return from inside a function body."
+            }
+            ErrorCode::ReturnFromInitializer => {
+                "An `init` method returns a value. Initializers always return the new
+instance, so a value can't be returned from them.
+
+Erroneous example:
+
+    class Point {
+        init(x) {
+            this.x = x;
+            return 1;
+        }
+    }
+
+Remove the value, or use a bare `return;` to leave the initializer early."
+            }
+            ErrorCode::InvalidThis => {
+                "`this` is used outside of a method, where there is no instance for it to
+refer to.
+
+Erroneous example:
+
+    fun describe() {
+        print this;
+    }
+
+Move the code into a method, or pass the instance in as a parameter."
+            }
+            ErrorCode::InvalidSuper => {
+                "`super` is used outside of a method of a class, where there is no
+superclass for it to refer to.
+
+Erroneous example:
+
+    fun f() {
+        super.g();
+    }
+
+Only call `super` methods from inside a subclass's methods."
+            }
+            ErrorCode::SuperWithoutSuperclass => {
+                "`super` is used in a class that doesn't inherit from another class.
+
+Erroneous example:
+
+    class Cake {
+        taste() {
+            super.taste();
+        }
+    }
+
+Declare a superclass with `class Cake < Dessert { ... }`, or remove the call."
+            }
+            ErrorCode::InheritFromSelf => {
+                "A class names itself as its superclass.
+
+Erroneous example:
+
+    class Oops < Oops {}
+
+Inherit from a different class, or leave out the `<` clause."
+            }
+            ErrorCode::UndefinedVariable => {
+                "A variable is read or assigned but was never declared.
+
+Erroneous example:
+
+    print count;
+
+Declare the variable with `var` before using it:
+
+    var count = 0;
+    print count;"
+            }
+            ErrorCode::UndefinedProperty => {
+                "An instance has no field or method with the given name.
+
+Erroneous example:
+
+    class Point {}
+    print Point().x;
+
+Set the field before reading it, for example in `init`, or check the name for
+typos."
+            }
+            ErrorCode::OperandType => {
+                "An operator was applied to values of the wrong type. Arithmetic and
+comparison need numbers, `+` needs two numbers or at least one string, and unary
+`-` needs a number.
+
+Erroneous example:
+
+    print \"total: \" - 3;
+    print nil < 1;
+
+Convert the values first, or use an operator that fits them."
+            }
+            ErrorCode::ArityMismatch => {
+                "A function, method or class was called with the wrong number of arguments.
+A class takes as many arguments as its `init` method, or none if it has no
+`init`.
+
+Erroneous example:
+
+    fun add(a, b) { return a + b; }
+    add(1);
+
+Pass exactly as many arguments as there are parameters."
+            }
+            ErrorCode::NotCallable => {
+                "Something that isn't a function or class was called.
+
+Erroneous example:
+
+    var name = \"lox\";
+    name();
+
+Only functions, methods and classes can be called."
+            }
+            ErrorCode::NotAnInstance => {
+                "A property was read or set on a value that isn't an instance. Only
+instances of classes have fields and methods.
+
+Erroneous example:
+
+    var n = 3;
+    print n.size;"
+            }
+            ErrorCode::SuperclassNotClass => {
+                "The expression after `<` in a class declaration doesn't evaluate to a class.
+
+Erroneous example:
+
+    var NotAClass = \"so not a class\";
+    class Subclass < NotAClass {}"
+            }
+            ErrorCode::NativeFunction => {
+                "A built-in function such as `clock` failed. The message describes what the
+host system reported."
+            }
+            ErrorCode::InvalidEscape => {
+                "A `\\` inside a string literal isn't followed by a valid escape sequence.
+
+Erroneous example:
+
+    print \"C:\\data\";
+    print \"\\u{110000}\";
+
+The escapes are `\\\"`, `\\\\`, `\\n`, `\\t`, `\\r`, `\\0`, `\\$` (for a literal `${`) and
+`\\u{...}` with one to six hex digits naming a Unicode scalar value. Write `\\\\` for a literal backslash:
+
+    print \"C:\\\\data\";
+    print \"\\u{1F600}\";"
+            }
+            ErrorCode::UnterminatedComment => {
+                "A block comment was opened with `/*` but the file ended before the
+matching `*/`.
+
+Erroneous example:
+
+    /* outer /* inner */
+    print 1;
+
+Block comments nest, so every `/*` inside a comment needs its own `*/`. The error
+points at the outermost `/*`:
+
+    /* outer /* inner */ */
+    print 1;"
+            }
+            ErrorCode::InvalidNumber => {
+                "A number literal is malformed.
+
+Erroneous example:
+
+    print 0x;
+    print 1e;
+    print 1_000_;
+    print 0b102;
+
+Numbers are written in decimal with an optional fraction and exponent, as in
+`12`, `1.5` or `1.5e-3`, or as integers in hexadecimal, binary or octal after
+`0x`, `0b` or `0o`, as in `0xff`, `0b1010` or `0o755`. A `_` may separate digits
+for readability, as in `1_000_000`, but only between two digits."
+            }
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "L{:04}", *self as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_round_trip() {
+        // Pinned so that a changed number fails here rather than silently
+        // breaking links to the documentation.
+        let expected = [
+            (ErrorCode::UnexpectedCharacter, "L0001"),
+            (ErrorCode::UnterminatedString, "L0002"),
+            (ErrorCode::ExpectedExpression, "L0003"),
+            (ErrorCode::ExpectedToken, "L0004"),
+            (ErrorCode::InvalidAssignmentTarget, "L0005"),
+            (ErrorCode::TooManyArguments, "L0006"),
+            (ErrorCode::DuplicateDeclaration, "L0007"),
+            (ErrorCode::ReadInOwnInitializer, "L0008"),
+            (ErrorCode::TopLevelReturn, "L0009"),
+            (ErrorCode::ReturnFromInitializer, "L0010"),
+            (ErrorCode::InvalidThis, "L0011"),
+            (ErrorCode::InvalidSuper, "L0012"),
+            (ErrorCode::SuperWithoutSuperclass, "L0013"),
+            (ErrorCode::InheritFromSelf, "L0014"),
+            (ErrorCode::UndefinedVariable, "L0015"),
+            (ErrorCode::UndefinedProperty, "L0016"),
+            (ErrorCode::OperandType, "L0017"),
+            (ErrorCode::ArityMismatch, "L0018"),
+            (ErrorCode::NotCallable, "L0019"),
+            (ErrorCode::NotAnInstance, "L0020"),
+            (ErrorCode::SuperclassNotClass, "L0021"),
+            (ErrorCode::NativeFunction, "L0022"),
+            (ErrorCode::InvalidEscape, "L0023"),
+            (ErrorCode::UnterminatedComment, "L0024"),
+            (ErrorCode::InvalidNumber, "L0025"),
+        ];
+        assert_eq!(expected.len(), ErrorCode::ALL.len());
+        for (code, number) in expected {
+            assert_eq!(code.to_string(), number);
+        }
+        for code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::from_code(&code.to_string()), Some(code));
+            assert!(!code.explain().is_empty());
+        }
+        assert_eq!(ErrorCode::from_code("l0015"), Some(ErrorCode::UndefinedVariable));
+        assert_eq!(ErrorCode::from_code("L9999"), None);
+    }
+}
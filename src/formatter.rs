@@ -0,0 +1,974 @@
+use std::collections::HashMap;
+
+use crate::{
+    cst::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken, SyntaxTree},
+    error::LoxError,
+    scanner::Scanner,
+    token::TokenType,
+};
+
+/// Columns to indent each nested block by.
+const INDENT: usize = 2;
+
+/// Why a program couldn't be formatted.
+#[derive(Debug)]
+pub enum FormatError {
+    /// The program doesn't parse.
+    Syntax(Vec<LoxError>),
+    /// Formatting changed more than whitespace, which is a bug in the
+    /// formatter. Writing the result would lose some of the program.
+    Changed,
+}
+
+/// Formats a Lox program in the one canonical style: two-space indentation,
+/// braces on the line that opens them, single spaces around binary operators,
+/// and lines wrapped to fit in `width` columns where the syntax allows.
+/// Comments are kept, and at most one blank line is kept between statements.
+pub fn format(source: &str, width: usize) -> Result<String, FormatError> {
+    let (tree, errors) = SyntaxTree::parse(source);
+    if !errors.is_empty() {
+        return Err(FormatError::Syntax(errors));
+    }
+    let formatter = Formatter::new(&tree.root);
+    let output = render(&formatter.program(&tree.root), width);
+    if lexemes(&output) != lexemes(source) {
+        return Err(FormatError::Changed);
+    }
+    Ok(output)
+}
+
+/// The text of every token and comment, which formatting must not change
+/// except for whitespace at the end of a comment.
+fn lexemes(source: &str) -> Vec<String> {
+    Scanner::with_trivia(source)
+        .filter_map(Result::ok)
+        .filter(|token| token.get_token_type() != TokenType::Whitespace)
+        .map(|token| token.get_lexeme().trim_end().to_string())
+        .collect()
+}
+
+/// A document describing the layouts a piece of code may take, after Wadler's
+/// "A prettier printer". A group is laid out on one line if it fits, and
+/// otherwise every line break directly in it becomes a newline.
+enum Doc {
+    Text(String),
+    /// A space, or a newline if the group is broken.
+    Line,
+    /// Nothing, or a newline if the group is broken.
+    SoftLine,
+    /// Always a newline; breaks every enclosing group.
+    HardLine,
+    /// A newline unless the current line is still empty. Breaks every
+    /// enclosing group.
+    FreshLine,
+    /// A `//` comment, after which the line must end. Breaks every enclosing
+    /// group.
+    LineComment(String),
+    Indent(Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+fn text(s: impl Into<String>) -> Doc {
+    Doc::Text(s.into())
+}
+
+fn indent(doc: Doc) -> Doc {
+    Doc::Indent(Box::new(doc))
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+impl Doc {
+    /// Whether the doc must break across lines wherever it is placed.
+    fn has_hard_break(&self) -> bool {
+        match self {
+            Doc::Text(s) => s.contains('\n'),
+            Doc::HardLine | Doc::FreshLine | Doc::LineComment(_) => true,
+            Doc::Line | Doc::SoftLine => false,
+            Doc::Indent(doc) | Doc::Group(doc) => doc.has_hard_break(),
+            Doc::Concat(docs) => docs.iter().any(Doc::has_hard_break),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+fn render(doc: &Doc, width: usize) -> String {
+    let mut out = Output::default();
+    let mut stack = vec![(0, Mode::Break, doc)];
+    while let Some((level, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(s) | Doc::LineComment(s) => {
+                if s.is_empty() {
+                    continue;
+                }
+                if out.line_comment {
+                    out.newline();
+                }
+                if out.line_start {
+                    out.text.push_str(&" ".repeat(level));
+                    out.column = level;
+                    out.line_start = false;
+                }
+                out.text.push_str(s);
+                match s.rfind('\n') {
+                    Some(i) => out.column = s[i + 1..].chars().count(),
+                    None => out.column += s.chars().count(),
+                }
+                out.line_comment = matches!(doc, Doc::LineComment(_));
+            }
+            Doc::Line | Doc::SoftLine if mode == Mode::Flat && !out.line_comment => {
+                if matches!(doc, Doc::Line) && !out.line_start {
+                    out.text.push(' ');
+                    out.column += 1;
+                }
+            }
+            Doc::Line | Doc::SoftLine | Doc::HardLine => out.newline(),
+            Doc::FreshLine => {
+                if !out.line_start {
+                    out.newline();
+                }
+            }
+            Doc::Indent(doc) => stack.push((level + INDENT, mode, doc)),
+            Doc::Group(doc) => {
+                let mode = if mode == Mode::Flat
+                    || (!doc.has_hard_break()
+                        && fits(doc, width.saturating_sub(out.column), &stack))
+                {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+                stack.push((level, mode, doc));
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (level, mode, doc))),
+        }
+    }
+    out.text
+}
+
+/// Rendered text so far, and where in it the next text goes.
+struct Output {
+    text: String,
+    column: usize,
+    // Indentation is written along with the first text on a line, so blank
+    // lines don't end up holding spaces.
+    line_start: bool,
+    // Set after a line comment, when the next text must go on a new line.
+    line_comment: bool,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            column: 0,
+            line_start: true,
+            line_comment: false,
+        }
+    }
+}
+
+impl Output {
+    fn newline(&mut self) {
+        self.text.truncate(self.text.trim_end_matches(' ').len());
+        self.text.push('\n');
+        self.column = 0;
+        self.line_start = true;
+        self.line_comment = false;
+    }
+}
+
+/// Whether `doc` laid out flat, and whatever follows it up to the next line
+/// break, fit in `remaining` columns.
+fn fits(doc: &Doc, mut remaining: usize, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+    loop {
+        let (mode, doc) = match stack.pop() {
+            Some(item) => item,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(s) => match remaining.checked_sub(s.chars().count()) {
+                Some(left) => remaining = left,
+                None => return false,
+            },
+            Doc::Line | Doc::SoftLine if mode == Mode::Flat => {
+                if matches!(doc, Doc::Line) {
+                    match remaining.checked_sub(1) {
+                        Some(left) => remaining = left,
+                        None => return false,
+                    }
+                }
+            }
+            Doc::Line | Doc::SoftLine | Doc::HardLine | Doc::FreshLine => return true,
+            // The line ends after the comment, however long it is.
+            Doc::LineComment(_) => return true,
+            Doc::Indent(doc) => stack.push((mode, doc)),
+            Doc::Group(doc) => stack.push((mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
+        }
+    }
+}
+
+/// A comment on a line of its own before a token.
+struct Comment {
+    text: String,
+    blank_line_before: bool,
+}
+
+/// What comes before a token in the source, besides whitespace.
+#[derive(Default)]
+struct Leading {
+    comments: Vec<Comment>,
+    // Block comments just before the token on its line.
+    inline: Vec<String>,
+    // Whether the token itself has a blank line before it.
+    blank_line_before: bool,
+}
+
+/// Builds docs from the syntax tree. Trivia is looked up by the offset of the
+/// token it surrounds rather than by where it sits in the tree.
+struct Formatter {
+    leading: HashMap<usize, Leading>,
+    // Comments on the same line as, and after, the token at each offset.
+    trailing: HashMap<usize, Vec<String>>,
+    end: usize,
+}
+
+impl Formatter {
+    fn new(root: &SyntaxNode) -> Self {
+        let mut tokens = Vec::new();
+        collect_tokens(root, &mut tokens);
+        let mut formatter = Formatter {
+            leading: HashMap::new(),
+            trailing: HashMap::new(),
+            end: root.span().end,
+        };
+        let mut previous = None;
+        let mut newlines = 0;
+        let mut leading = Leading::default();
+        for (i, token) in tokens.iter().enumerate() {
+            match token.kind() {
+                TokenType::Whitespace => newlines += token.text().matches('\n').count(),
+                TokenType::Comment => {
+                    // A block comment with code after it on the same line, as in
+                    // `(/* c */ x)`, stays beside that code.
+                    let inline = token.text().starts_with("/*")
+                        && precedes_code_on_line(&tokens[i + 1..])
+                        && previous
+                            .is_none_or(|(_, kind)| newlines > 0 || kind == TokenType::LeftParen);
+                    match previous {
+                        _ if inline => leading.inline.push(token.text().to_string()),
+                        Some((previous, _)) if newlines == 0 => formatter
+                            .trailing
+                            .entry(previous)
+                            .or_default()
+                            .push(token.text().trim_end().to_string()),
+                        _ => leading.comments.push(Comment {
+                            text: token.text().trim_end().to_string(),
+                            blank_line_before: newlines >= 2,
+                        }),
+                    }
+                    if !inline {
+                        newlines = 0;
+                    }
+                }
+                _ => {
+                    leading.blank_line_before = newlines >= 2;
+                    let start = token.span().start;
+                    formatter
+                        .leading
+                        .insert(start, std::mem::take(&mut leading));
+                    previous = Some((start, token.kind()));
+                    newlines = 0;
+                }
+            }
+        }
+        leading.blank_line_before = newlines >= 2;
+        formatter.leading.insert(formatter.end, leading);
+        formatter
+    }
+
+    fn program(&self, root: &SyntaxNode) -> Doc {
+        let statements = child_nodes(root);
+        let mut docs = vec![self.statements(&statements)];
+        let eof = &self.leading[&self.end];
+        for (i, comment) in eof.comments.iter().enumerate() {
+            if i > 0 || !statements.is_empty() {
+                docs.push(Doc::HardLine);
+                if comment.blank_line_before {
+                    docs.push(Doc::HardLine);
+                }
+            }
+            docs.push(text(&comment.text));
+        }
+        if !statements.is_empty() || !eof.comments.is_empty() {
+            docs.push(Doc::HardLine);
+        }
+        Doc::Concat(docs)
+    }
+
+    /// Statements one per line, keeping single blank lines between them.
+    fn statements(&self, statements: &[SyntaxNode]) -> Doc {
+        let mut docs = Vec::new();
+        for (i, statement) in statements.iter().enumerate() {
+            if i > 0 {
+                docs.push(Doc::HardLine);
+                if self.blank_line_before(statement) {
+                    docs.push(Doc::HardLine);
+                }
+            }
+            docs.push(self.statement(statement));
+        }
+        Doc::Concat(docs)
+    }
+
+    fn blank_line_before(&self, node: &SyntaxNode) -> bool {
+        let leading = &self.leading[&first_token(node).span().start];
+        match leading.comments.first() {
+            Some(comment) => comment.blank_line_before,
+            None => leading.blank_line_before,
+        }
+    }
+
+    /// A token with the comments around it.
+    fn token(&self, token: &SyntaxToken) -> Doc {
+        let start = token.span().start;
+        let mut docs = Vec::new();
+        let leading = &self.leading[&start];
+        for (i, comment) in leading.comments.iter().enumerate() {
+            docs.push(Doc::FreshLine);
+            if i > 0 && comment.blank_line_before {
+                docs.push(Doc::HardLine);
+            }
+            docs.push(text(&comment.text));
+            docs.push(Doc::HardLine);
+        }
+        if !leading.comments.is_empty() && leading.blank_line_before {
+            docs.push(Doc::HardLine);
+        }
+        for comment in &leading.inline {
+            docs.push(text(comment));
+            if !matches!(token.kind(), TokenType::RightParen | TokenType::Semicolon) {
+                docs.push(text(" "));
+            }
+        }
+        docs.push(text(token.text()));
+        docs.push(self.trailing(token));
+        Doc::Concat(docs)
+    }
+
+    /// Comments after `token` on its line, which stay beside it. A line
+    /// comment ends the line, so whatever follows goes on the next one.
+    fn trailing(&self, token: &SyntaxToken) -> Doc {
+        let comments = self.trailing.get(&token.span().start).into_iter().flatten();
+        Doc::Concat(
+            comments
+                .map(|comment| {
+                    if comment.starts_with("//") {
+                        Doc::LineComment(format!(" {comment}"))
+                    } else {
+                        text(format!(" {comment}"))
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn statement(&self, node: &SyntaxNode) -> Doc {
+        let parts = significant(node);
+        let mut parts = parts.iter();
+        let mut docs = Vec::new();
+        match node.kind() {
+            SyntaxKind::Block => return self.block(node),
+            SyntaxKind::Class => return self.class(node),
+            SyntaxKind::Function => return self.function(node),
+            SyntaxKind::Expression => {
+                docs.push(self.element(parts.next().unwrap()));
+                docs.push(self.element(parts.next().unwrap()));
+            }
+            SyntaxKind::Print | SyntaxKind::Return => {
+                docs.push(self.element(parts.next().unwrap()));
+                for part in parts {
+                    if let SyntaxElement::Node(_) = part {
+                        docs.push(text(" "));
+                    }
+                    docs.push(self.element(part));
+                }
+            }
+            SyntaxKind::Var => {
+                // var name [= value];
+                docs.push(self.element(parts.next().unwrap()));
+                docs.push(text(" "));
+                for part in parts {
+                    match part {
+                        SyntaxElement::Token(token) if token.kind() == TokenType::Equal => {
+                            docs.push(text(" "));
+                            docs.push(self.token(token));
+                            docs.push(text(" "));
+                        }
+                        part => docs.push(self.element(part)),
+                    }
+                }
+            }
+            SyntaxKind::If => {
+                // if ( condition ) then [else otherwise]
+                for _ in 0..4 {
+                    docs.push(self.element(parts.next().unwrap()));
+                }
+                docs.insert(1, text(" "));
+                let then = as_node(parts.next().unwrap());
+                docs.push(self.body(then));
+                if let Some(SyntaxElement::Token(keyword)) = parts.next() {
+                    docs.push(if then.kind() == SyntaxKind::Block {
+                        text(" ")
+                    } else {
+                        Doc::HardLine
+                    });
+                    docs.push(self.token(keyword));
+                    let otherwise = as_node(parts.next().unwrap());
+                    if otherwise.kind() == SyntaxKind::If {
+                        docs.push(text(" "));
+                        docs.push(self.statement(otherwise));
+                    } else {
+                        docs.push(self.body(otherwise));
+                    }
+                }
+            }
+            SyntaxKind::While => {
+                // while ( condition ) body
+                for _ in 0..4 {
+                    docs.push(self.element(parts.next().unwrap()));
+                }
+                docs.insert(1, text(" "));
+                docs.push(self.body(as_node(parts.next().unwrap())));
+            }
+            SyntaxKind::For => {
+                // for ( initializer|; [condition] ; [increment] ) body
+                docs.push(self.element(parts.next().unwrap()));
+                docs.push(text(" "));
+                docs.push(self.element(parts.next().unwrap()));
+                docs.push(self.element(parts.next().unwrap()));
+                let mut clauses = 0;
+                let mut body = None;
+                for part in parts {
+                    match part {
+                        SyntaxElement::Token(token) => {
+                            clauses += 1;
+                            docs.push(self.token(token));
+                        }
+                        SyntaxElement::Node(node) if clauses == 2 => body = Some(node),
+                        SyntaxElement::Node(node) => {
+                            docs.push(text(" "));
+                            docs.push(self.expression(node));
+                        }
+                    }
+                }
+                docs.push(self.body(body.unwrap()));
+            }
+            kind => unreachable!("{kind:?} is not a statement"),
+        }
+        Doc::Concat(docs)
+    }
+
+    /// The body of an `if`, `else`, `while` or `for`: a block stays on the same
+    /// line, and any other statement too if it fits.
+    fn body(&self, node: &SyntaxNode) -> Doc {
+        if node.kind() == SyntaxKind::Block {
+            Doc::Concat(vec![text(" "), self.block(node)])
+        } else {
+            group(indent(Doc::Concat(vec![Doc::Line, self.statement(node)])))
+        }
+    }
+
+    fn block(&self, node: &SyntaxNode) -> Doc {
+        let parts = significant(node);
+        let (open, close) = (as_token(&parts[0]), as_token(&parts[parts.len() - 1]));
+        let statements = child_nodes(node);
+        self.braces(open, &statements, close)
+    }
+
+    /// `{`, the statements or methods inside, each on its own line, and `}`.
+    fn braces(&self, open: &SyntaxToken, inner: &[SyntaxNode], close: &SyntaxToken) -> Doc {
+        let closing = &self.leading[&close.span().start];
+        if inner.is_empty() && closing.comments.is_empty() {
+            return Doc::Concat(vec![self.token(open), self.token(close)]);
+        }
+        let mut body = vec![Doc::HardLine, self.statements(inner)];
+        // Comments before the '}' belong to the inside of the block.
+        for (i, comment) in closing.comments.iter().enumerate() {
+            if i > 0 || !inner.is_empty() {
+                body.push(Doc::HardLine);
+                if comment.blank_line_before {
+                    body.push(Doc::HardLine);
+                }
+            }
+            body.push(text(&comment.text));
+        }
+        Doc::Concat(vec![
+            self.token(open),
+            indent(Doc::Concat(body)),
+            Doc::HardLine,
+            text(close.text()),
+            self.trailing(close),
+        ])
+    }
+
+    fn class(&self, node: &SyntaxNode) -> Doc {
+        // class Name [< Superclass] { methods }
+        let parts = significant(node);
+        let mut docs = Vec::new();
+        let mut methods = Vec::new();
+        let mut open = None;
+        for part in &parts[..parts.len() - 1] {
+            match part {
+                SyntaxElement::Token(token) if token.kind() == TokenType::LeftBrace => {
+                    open = Some(token)
+                }
+                SyntaxElement::Node(node) if node.kind() == SyntaxKind::Function => {
+                    methods.push(node.clone())
+                }
+                part => {
+                    if !docs.is_empty() {
+                        docs.push(text(" "));
+                    }
+                    docs.push(self.element(part));
+                }
+            }
+        }
+        docs.push(text(" "));
+        docs.push(self.braces(open.unwrap(), &methods, as_token(&parts[parts.len() - 1])));
+        Doc::Concat(docs)
+    }
+
+    fn function(&self, node: &SyntaxNode) -> Doc {
+        // [fun] name ( parameters ) body
+        let mut docs = Vec::new();
+        let mut parameters = Vec::new();
+        let mut in_parameters = false;
+        for part in significant(node) {
+            match &part {
+                SyntaxElement::Token(token) if token.kind() == TokenType::Fun => {
+                    docs.push(self.token(token));
+                    docs.push(text(" "));
+                }
+                SyntaxElement::Token(token) if token.kind() == TokenType::LeftParen => {
+                    in_parameters = true;
+                    parameters.push(part.clone());
+                }
+                SyntaxElement::Token(token) if token.kind() == TokenType::RightParen => {
+                    in_parameters = false;
+                    parameters.push(part.clone());
+                    docs.push(self.list(&parameters));
+                }
+                _ if in_parameters => parameters.push(part.clone()),
+                SyntaxElement::Node(body) => {
+                    docs.push(text(" "));
+                    docs.push(self.block(body));
+                }
+                SyntaxElement::Token(token) => docs.push(self.token(token)),
+            }
+        }
+        Doc::Concat(docs)
+    }
+
+    /// A parenthesized, comma separated list of parameters or arguments, which
+    /// puts each item on its own line if they don't all fit on one.
+    fn list(&self, parts: &[SyntaxElement]) -> Doc {
+        let (open, close) = (&parts[0], &parts[parts.len() - 1]);
+        let items = &parts[1..parts.len() - 1];
+        if items.is_empty() {
+            return Doc::Concat(vec![self.element(open), self.element(close)]);
+        }
+        let mut inner = vec![Doc::SoftLine];
+        for item in items {
+            match item {
+                SyntaxElement::Token(comma) if comma.kind() == TokenType::Comma => {
+                    inner.push(self.token(comma));
+                    inner.push(Doc::Line);
+                }
+                item => inner.push(self.element(item)),
+            }
+        }
+        group(Doc::Concat(vec![
+            self.element(open),
+            indent(Doc::Concat(inner)),
+            Doc::SoftLine,
+            self.element(close),
+        ]))
+    }
+
+    fn element(&self, element: &SyntaxElement) -> Doc {
+        match element {
+            SyntaxElement::Token(token) => self.token(token),
+            SyntaxElement::Node(node) if is_statement(node.kind()) => self.statement(node),
+            SyntaxElement::Node(node) => self.expression(node),
+        }
+    }
+
+    fn expression(&self, node: &SyntaxNode) -> Doc {
+        let parts = significant(node);
+        match node.kind() {
+            SyntaxKind::Binary | SyntaxKind::Logical => {
+                // `a + b + c` breaks after every `+` or none, keeping the
+                // operators at the ends of lines.
+                let mut operands = vec![parts[2].clone()];
+                let mut operators = vec![as_token(&parts[1]).clone()];
+                let mut first = parts[0].clone();
+                while let SyntaxElement::Node(left) = &first {
+                    let left_parts = significant(left);
+                    if left.kind() != node.kind()
+                        || precedence(as_token(&left_parts[1]).kind())
+                            != precedence(operators[0].kind())
+                    {
+                        break;
+                    }
+                    operands.push(left_parts[2].clone());
+                    operators.push(as_token(&left_parts[1]).clone());
+                    first = left_parts[0].clone();
+                }
+                let mut rest = Vec::new();
+                for (operator, operand) in operators.iter().zip(&operands).rev() {
+                    rest.push(text(" "));
+                    rest.push(self.token(operator));
+                    rest.push(Doc::Line);
+                    rest.push(self.element(operand));
+                }
+                group(Doc::Concat(vec![
+                    self.element(&first),
+                    indent(Doc::Concat(rest)),
+                ]))
+            }
+            SyntaxKind::Assign | SyntaxKind::Set => Doc::Concat(vec![
+                self.element(&parts[0]),
+                text(" "),
+                self.element(&parts[1]),
+                text(" "),
+                self.element(&parts[2]),
+            ]),
+            SyntaxKind::Call => Doc::Concat(vec![self.element(&parts[0]), self.list(&parts[1..])]),
+            SyntaxKind::Interpolation => {
+                // Line breaks inside `${...}` would be legal but hard to read,
+                // so there are none unless a comment needs them.
+                let docs = parts.iter().map(|part| match part {
+                    SyntaxElement::Node(node) => {
+                        let doc = self.expression(node);
+                        if doc.has_hard_break() {
+                            doc
+                        } else {
+                            text(render(&doc, usize::MAX))
+                        }
+                    }
+                    SyntaxElement::Token(token) => self.token(token),
+                });
+                Doc::Concat(docs.collect())
+            }
+            // Everything else is its tokens with nothing between them.
+            _ => Doc::Concat(parts.iter().map(|part| self.element(part)).collect()),
+        }
+    }
+}
+
+/// Groups binary operators that bind equally tightly.
+fn precedence(operator: TokenType) -> u8 {
+    match operator {
+        TokenType::Or => 0,
+        TokenType::And => 1,
+        TokenType::EqualEqual | TokenType::BangEqual => 2,
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => 3,
+        TokenType::Plus | TokenType::Minus => 4,
+        _ => 5,
+    }
+}
+
+fn is_statement(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::Block
+            | SyntaxKind::Class
+            | SyntaxKind::Expression
+            | SyntaxKind::For
+            | SyntaxKind::Function
+            | SyntaxKind::If
+            | SyntaxKind::Print
+            | SyntaxKind::Return
+            | SyntaxKind::Var
+            | SyntaxKind::While
+    )
+}
+
+/// The children of `node` that aren't whitespace or comments.
+fn significant(node: &SyntaxNode) -> Vec<SyntaxElement> {
+    node.children()
+        .into_iter()
+        .filter(|child| match child {
+            SyntaxElement::Token(token) => !token.kind().is_trivia(),
+            SyntaxElement::Node(_) => true,
+        })
+        .collect()
+}
+
+fn child_nodes(node: &SyntaxNode) -> Vec<SyntaxNode> {
+    node.children()
+        .into_iter()
+        .filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+        .collect()
+}
+
+fn first_token(node: &SyntaxNode) -> SyntaxToken {
+    match &significant(node)[0] {
+        SyntaxElement::Token(token) => token.clone(),
+        SyntaxElement::Node(node) => first_token(node),
+    }
+}
+
+/// Whether the next thing after some trivia is code on the same line.
+fn precedes_code_on_line(rest: &[SyntaxToken]) -> bool {
+    for token in rest {
+        match token.kind() {
+            TokenType::Whitespace if token.text().contains('\n') => return false,
+            TokenType::Whitespace => {}
+            kind => return kind != TokenType::Comment,
+        }
+    }
+    false
+}
+
+fn collect_tokens(node: &SyntaxNode, tokens: &mut Vec<SyntaxToken>) {
+    for child in node.children() {
+        match child {
+            SyntaxElement::Token(token) => tokens.push(token),
+            SyntaxElement::Node(node) => collect_tokens(&node, tokens),
+        }
+    }
+}
+
+fn as_node(element: &SyntaxElement) -> &SyntaxNode {
+    match element {
+        SyntaxElement::Node(node) => node,
+        SyntaxElement::Token(token) => panic!("expected a node, found {:?}", token.kind()),
+    }
+}
+
+fn as_token(element: &SyntaxElement) -> &SyntaxToken {
+    match element {
+        SyntaxElement::Token(token) => token,
+        SyntaxElement::Node(node) => panic!("expected a token, found {:?}", node.kind()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(source: &str) -> String {
+        format(source, 80).unwrap()
+    }
+
+    /// `source` with its indentation removed and every other space tripled.
+    fn mangle(source: &str) -> String {
+        Scanner::with_trivia(source)
+            .map(|token| {
+                let token = token.unwrap();
+                match token.get_token_type() {
+                    TokenType::Whitespace if token.get_lexeme().contains('\n') => {
+                        "\n".repeat(token.get_lexeme().matches('\n').count())
+                    }
+                    TokenType::Whitespace => "   ".to_string(),
+                    _ => token.get_lexeme().to_string(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_examples_are_golden() {
+        // The examples are written in the house style, so formatting them,
+        // even after messing up their layout, must give them back unchanged.
+        for entry in std::fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            let source = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(fmt(&source), source, "{}", path.display());
+            assert_eq!(fmt(&mangle(&source)), source, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn test_spacing_and_braces() {
+        let source = "fun f(a,b){if(a<b)return a;else{return -b;}}\n\
+            class A<B{init(){this.x=nil;}}for(;;){}\n\
+            if (a) print 1; else if (!b) print 2; else { print \"${a+1}\"; }";
+        let expected = "\
+fun f(a, b) {
+  if (a < b) return a;
+  else {
+    return -b;
+  }
+}
+class A < B {
+  init() {
+    this.x = nil;
+  }
+}
+for (;;) {}
+if (a) print 1;
+else if (!b) print 2;
+else {
+  print \"${a + 1}\";
+}
+";
+        assert_eq!(fmt(source), expected);
+        assert_eq!(fmt(expected), expected);
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines() {
+        let source = "// header\n\n\n\nvar a = /* one */ 1; // first\n\
+            print a;\n  // own line\n\n\nprint a\n  // before the semicolon\n  ;\n\
+            {\n// inside\n}\nclass C {\n  m() {}\n\n\n  // last\n}\n// end\n";
+        let expected = "\
+// header
+
+var a = /* one */ 1; // first
+print a;
+// own line
+
+print a
+// before the semicolon
+;
+{
+  // inside
+}
+class C {
+  m() {}
+
+  // last
+}
+// end
+";
+        assert_eq!(fmt(source), expected);
+        assert_eq!(fmt(expected), expected);
+    }
+
+    #[test]
+    fn test_line_comments_end_lines() {
+        // Whatever followed a trailing `//` comment moves to the next line,
+        // breaking the group the comment is in.
+        for (source, expected) in [
+            ("print a + // c\n b;", "print a + // c\n  b;\n"),
+            (
+                "print f(a, // first\n b);",
+                "print f(\n  a, // first\n  b\n);\n",
+            ),
+            ("if (a) // why\n  print 1;", "if (a) // why\n  print 1;\n"),
+            (
+                "for (var i = 0; i < 3; i = i + 1) // c\n  print i;",
+                "for (var i = 0; i < 3; i = i + 1) // c\n  print i;\n",
+            ),
+            ("print \"${a // c\n}\";", "print \"${a // c\n}\";\n"),
+            ("print \"${ // c\na + 1}\";", "print \"${ // c\na + 1}\";\n"),
+        ] {
+            assert_eq!(fmt(source), expected, "{source:?}");
+            assert_eq!(fmt(expected), expected, "{source:?}");
+        }
+    }
+
+    #[test]
+    fn test_comment_after_every_token() {
+        for entry in std::fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            let source = std::fs::read_to_string(&path).unwrap();
+            for comment in [" // c\n", " /* c */ "] {
+                let commented: String = Scanner::with_trivia(&source)
+                    .map(|token| {
+                        let token = token.unwrap();
+                        match token.get_token_type() {
+                            TokenType::Whitespace | TokenType::Comment | TokenType::Eof => {
+                                token.get_lexeme().to_string()
+                            }
+                            _ => format!("{}{comment}", token.get_lexeme()),
+                        }
+                    })
+                    .collect();
+                let once = fmt(&commented);
+                assert_eq!(fmt(&once), once, "{}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn test_block_comment_after_paren() {
+        for (source, expected) in [
+            ("print (/* c */ 1);", "print (/* c */ 1);\n"),
+            ("for (/* a */ ; ;) {}", "for (/* a */;;) {}\n"),
+            ("f( /* none */ );", "f(/* none */);\n"),
+            (
+                "f(a /* one */, /* two */ b);",
+                "f(a /* one */, /* two */ b);\n",
+            ),
+            ("{\n/* c */ print 1;\n}", "{\n  /* c */ print 1;\n}\n"),
+        ] {
+            assert_eq!(fmt(source), expected, "{source:?}");
+            assert_eq!(fmt(expected), expected, "{source:?}");
+        }
+    }
+
+    #[test]
+    fn test_crlf_source() {
+        let source = "// header\r\nprint 1; // one  \r\n\r\n/* two */\r\nprint 2;\r\n";
+        let expected = "// header\nprint 1; // one\n\n/* two */\nprint 2;\n";
+        assert_eq!(fmt(source), expected);
+    }
+
+    #[test]
+    fn test_wraps_to_width() {
+        let source = "print f(first, second, third) + g(fourth) + h(fifth, sixth);";
+        assert_eq!(format(source, 80).unwrap(), format!("{source}\n"));
+        let expected = "\
+print f(first, second, third) +
+  g(fourth) +
+  h(fifth, sixth);
+";
+        assert_eq!(format(source, 32).unwrap(), expected);
+        let expected = "\
+print f(
+  first,
+  second,
+  third
+) +
+  g(fourth) +
+  h(
+    fifth,
+    sixth
+  );
+";
+        assert_eq!(format(source, 16).unwrap(), expected);
+        for width in 1..64 {
+            let once = format(source, width).unwrap();
+            assert_eq!(format(&once, width).unwrap(), once, "width {width}");
+        }
+    }
+
+    #[test]
+    fn test_rejects_syntax_errors() {
+        match format("print 1 +;\nvar;", 80) {
+            Err(FormatError::Syntax(errors)) => assert_eq!(errors.len(), 2),
+            result => panic!("expected syntax errors, got {result:?}"),
+        }
+    }
+}
@@ -1,122 +1,482 @@
-use crate::{expr::{ExprVisitor, Expr}, token::{Literal, TokenType}, error::LoxError};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-pub struct Interpreter {}
+use crate::{
+    callable::LoxCallable,
+    environment::Environment,
+    error::LoxError,
+    error_code::ErrorCode,
+    expr::{BinaryExpr, Expr, ExprVisitorMut},
+    lox_class::LoxClass,
+    lox_function::LoxFunction,
+    lox_instance::LoxInstance,
+    native_functions::natives,
+    stmt::*,
+    token::{Token, TokenType},
+    value::Value,
+};
 
-impl ExprVisitor<Literal> for Interpreter {
-    fn visit_binary_expr(&self, expr: &crate::expr::BinaryExpr) -> Result<Literal, crate::error::LoxError> {
-        let left = self.evaluate(&expr.left)?;
-        let right = self.evaluate(&expr.right)?;
-        let op = expr.operator.get_token_type();
-
-        let result = match (left, right) {
-            (Literal::Number(left), Literal::Number(right)) => match op {
-                TokenType::Minus => Literal::Number(left - right),
-                TokenType::Slash => Literal::Number(left / right),
-                TokenType::Star => Literal::Number(left * right),
-                TokenType::Plus => Literal::Number(left + right),
-                TokenType::Greater => Literal::Bool(left > right),
-                TokenType::GreaterEqual => Literal::Bool(left >= right),
-                TokenType::Less => Literal::Bool(left < right),
-                TokenType::LessEqual => Literal::Bool(left <= right),
-                TokenType::BangEqual => Literal::Bool(left != right),
-                TokenType::Equal => Literal::Bool(left == right),
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    locals: HashMap<usize, usize>,
+}
+
+/// How control leaves a statement: by running off its end, or by unwinding a `return`.
+pub enum Flow {
+    Normal,
+    Return(Value),
+}
+
+impl StmtVisitorMut<Flow> for Interpreter {
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) -> Result<Flow, LoxError> {
+        let environment = Environment::new_with_enclosing(Rc::clone(&self.environment));
+        self.execute_block(&stmt.statements, environment)
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) -> Result<Flow, LoxError> {
+        let superclass = match &stmt.superclass {
+            Some(superclass) => match self.evaluate(superclass)? {
+                Value::Class(klass) => Some(klass),
                 _ => {
-                    todo!("need to work on your code dude");
+                    let token = match &**superclass {
+                        Expr::Variable(variable) => variable.name.clone(),
+                        _ => stmt.name.clone(),
+                    };
+                    return Err(LoxError::new_with_token(
+                        ErrorCode::SuperclassNotClass,
+                        token,
+                        "Superclass must be a class.".to_string(),
+                    ));
                 }
             },
-            (Literal::Number(left), Literal::String(right)) => match op {
-                TokenType::Plus => Literal::String(format!("{left}{right}")),
-                _ => Literal::ArithmeticError,
-            },
-            (Literal::String(left), Literal::Number(right)) => match op {
-                TokenType::Plus => Literal::String(format!("{left}{right}")),
-                _ => Literal::ArithmeticError,
-            },
-            (Literal::String(left), Literal::String(right)) => match op {
-                TokenType::Plus => Literal::String(format!("{left}{right}")),
-                TokenType::BangEqual => Literal::Bool(left != right),
-                TokenType::Equal => Literal::Bool(left == right),
-                _ => Literal::ArithmeticError,
-            },
-            (Literal::Bool(left), Literal::Bool(right)) => match op {
-                TokenType::BangEqual => Literal::Bool(left != right),
-                TokenType::Equal => Literal::Bool(left == right),
-                _ => Literal::ArithmeticError,
-            },
-            (Literal::Nil, Literal::Nil) => match op {
-                TokenType::BangEqual => Literal::Bool(false),
-                TokenType::Equal => Literal::Bool(true),
-                _ => Literal::ArithmeticError,
-            },
-            (Literal::Nil, _) => match op {
-                TokenType::Equal => Literal::Bool(false),
-                TokenType::BangEqual => Literal::Bool(true),
-                _ => Literal::ArithmeticError,
-            },
-            _ => Literal::ArithmeticError,
+            None => None,
         };
 
-        if result == Literal::ArithmeticError {
-            Err(LoxError::new_with_token(
-                expr.operator.clone(),
-                "Illegal expression".to_string(),
-            ))
+        self.environment
+            .borrow_mut()
+            .define(stmt.name.get_lexeme(), Value::Nil);
+
+        // Methods of a subclass close over an extra scope that holds `super`.
+        let previous = Rc::clone(&self.environment);
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::new_with_enclosing(Rc::clone(&previous));
+            environment.define("super", Value::Class(Rc::clone(superclass)));
+            self.environment = Rc::new(RefCell::new(environment));
+        }
+
+        let mut methods = HashMap::new();
+        for method in &stmt.methods {
+            let name = method.name.get_lexeme();
+            let function = LoxFunction::new(
+                method,
+                Rc::clone(&self.environment),
+                name == "init",
+            );
+            methods.insert(name.to_string(), Rc::new(function));
+        }
+
+        let klass = LoxClass::new(stmt.name.get_lexeme().to_string(), superclass, methods);
+        self.environment = previous;
+
+        self.environment
+            .borrow_mut()
+            .assign(&stmt.name, Value::Class(Rc::new(klass)))?;
+        Ok(Flow::Normal)
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &ExpressionStmt) -> Result<Flow, LoxError> {
+        self.evaluate(&stmt.expression)?;
+        Ok(Flow::Normal)
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &FunctionStmt) -> Result<Flow, LoxError> {
+        let function = LoxFunction::new(stmt, Rc::clone(&self.environment), false);
+        self.environment.borrow_mut().define(
+            stmt.name.get_lexeme(),
+            Value::Function(Rc::new(function)),
+        );
+        Ok(Flow::Normal)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) -> Result<Flow, LoxError> {
+        let condition = self.evaluate(&stmt.condition)?;
+        if self.is_truthy(&condition) {
+            self.execute(&stmt.then_branch)
+        } else if let Some(else_branch) = &stmt.else_branch {
+            self.execute(else_branch)
         } else {
-            Ok(result)
+            Ok(Flow::Normal)
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) -> Result<Flow, LoxError> {
+        let value = self.evaluate(&stmt.expression)?;
+        println!("{value}");
+        Ok(Flow::Normal)
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) -> Result<Flow, LoxError> {
+        let value = match &stmt.value {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Nil,
+        };
+        Ok(Flow::Return(value))
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) -> Result<Flow, LoxError> {
+        let value = match &stmt.initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Value::Nil,
+        };
+
+        self.environment
+            .borrow_mut()
+            .define(stmt.name.get_lexeme(), value);
+        Ok(Flow::Normal)
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) -> Result<Flow, LoxError> {
+        loop {
+            let condition = self.evaluate(&stmt.condition)?;
+            if !self.is_truthy(&condition) {
+                break;
+            }
+            if let Flow::Return(value) = self.execute(&stmt.body)? {
+                return Ok(Flow::Return(value));
+            }
+        }
+        Ok(Flow::Normal)
+    }
+}
+
+impl ExprVisitorMut<Value> for Interpreter {
+    fn visit_assign_expr(&mut self, expr: &crate::expr::AssignExpr) -> Result<Value, crate::error::LoxError> {
+        let value = self.evaluate(&expr.value)?;
+        match self.locals.get(&expr.id) {
+            Some(distance) => self.environment.borrow_mut().assign_at(
+                *distance,
+                &expr.name,
+                value.clone(),
+            )?,
+            None => self.globals.borrow_mut().assign(&expr.name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    fn visit_binary_expr(&mut self, expr: &crate::expr::BinaryExpr) -> Result<Value, crate::error::LoxError> {
+        let left = self.evaluate(&expr.left)?;
+        let right = self.evaluate(&expr.right)?;
+        let operator = &expr.operator;
+
+        match operator.get_token_type() {
+            TokenType::BangEqual => Ok(Value::Bool(left != right)),
+            TokenType::EqualEqual => Ok(Value::Bool(left == right)),
+            TokenType::Plus => match (left, right) {
+                (Value::Number(left), Value::Number(right)) => Ok(Value::Number(left + right)),
+                // A number added to a string is stringified and concatenated.
+                (Value::String(left), Value::String(right)) => Ok(Value::String(format!("{left}{right}").into())),
+                (Value::String(left), Value::Number(right)) => Ok(Value::String(format!("{left}{right}").into())),
+                (Value::Number(left), Value::String(right)) => Ok(Value::String(format!("{left}{right}").into())),
+                (left, right) => Err(Interpreter::operand_error(
+                    expr,
+                    &left,
+                    &right,
+                    "Operands must be two numbers or two strings.",
+                )),
+            },
+            TokenType::Minus => {
+                let (left, right) = self.number_operands(expr, &left, &right)?;
+                Ok(Value::Number(left - right))
+            }
+            TokenType::Slash => {
+                let (left, right) = self.number_operands(expr, &left, &right)?;
+                Ok(Value::Number(left / right))
+            }
+            TokenType::Star => {
+                let (left, right) = self.number_operands(expr, &left, &right)?;
+                Ok(Value::Number(left * right))
+            }
+            TokenType::Greater => {
+                let (left, right) = self.number_operands(expr, &left, &right)?;
+                Ok(Value::Bool(left > right))
+            }
+            TokenType::GreaterEqual => {
+                let (left, right) = self.number_operands(expr, &left, &right)?;
+                Ok(Value::Bool(left >= right))
+            }
+            TokenType::Less => {
+                let (left, right) = self.number_operands(expr, &left, &right)?;
+                Ok(Value::Bool(left < right))
+            }
+            TokenType::LessEqual => {
+                let (left, right) = self.number_operands(expr, &left, &right)?;
+                Ok(Value::Bool(left <= right))
+            }
+            _ => unreachable!("the parser only builds binary expressions from binary operators"),
         }
     }
 
-    fn visit_grouping_expr(&self, expr: &crate::expr::GroupingExpr) -> Result<Literal, crate::error::LoxError> {
-        Ok(self.evaluate(&expr.expression)?)
+    fn visit_call_expr(&mut self, expr: &crate::expr::CallExpr) -> Result<Value, crate::error::LoxError> {
+        let callee = self.evaluate(&expr.callee)?;
+
+        let mut arguments = Vec::new();
+        for argument in &expr.arguments {
+            arguments.push(self.evaluate(argument)?);
+        }
+
+        match callee {
+            Value::Function(function) => {
+                self.check_arity(&expr.paren, function.arity(), arguments.len())?;
+                function.call(self, arguments)
+            }
+            Value::Native(function) => {
+                self.check_arity(&expr.paren, function.arity(), arguments.len())?;
+                function.call(self, arguments)
+            }
+            Value::Class(klass) => {
+                self.check_arity(&expr.paren, klass.arity(), arguments.len())?;
+                LoxClass::instantiate(&klass, self, arguments)
+            }
+            callee => Err(LoxError::new_with_token(
+                ErrorCode::NotCallable,
+                expr.paren.clone(),
+                "Can only call functions and classes.".to_string(),
+            )
+            .with_secondary(expr.callee.span(), callee.type_name())),
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &crate::expr::GetExpr) -> Result<Value, crate::error::LoxError> {
+        match self.evaluate(&expr.object)? {
+            Value::Instance(instance) => LoxInstance::get(&instance, &expr.name),
+            _ => Err(LoxError::new_with_token(
+                ErrorCode::NotAnInstance,
+                expr.name.clone(),
+                "Only instances have properties.".to_string(),
+            )),
+        }
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &crate::expr::GroupingExpr) -> Result<Value, crate::error::LoxError> {
+        self.evaluate(&expr.expression)
+    }
+
+    fn visit_interpolation_expr(&mut self, expr: &crate::expr::InterpolationExpr) -> Result<Value, crate::error::LoxError> {
+        let mut result = String::new();
+        for part in &expr.parts {
+            result.push_str(&self.evaluate(part)?.to_string());
+        }
+        Ok(Value::String(Rc::from(result)))
+    }
+
+    fn visit_literal_expr(&mut self, expr: &crate::expr::LiteralExpr) -> Result<Value, crate::error::LoxError> {
+        match &expr.value {
+            Some(literal) => Ok(Value::from(literal)),
+            None => Ok(Value::Nil),
+        }
+    }
+
+    fn visit_logical_expr(&mut self, expr: &crate::expr::LogicalExpr) -> Result<Value, crate::error::LoxError> {
+        let left = self.evaluate(&expr.left)?;
+
+        // Short-circuit and hand back the deciding operand itself.
+        if expr.operator.get_token_type() == TokenType::Or {
+            if self.is_truthy(&left) {
+                return Ok(left);
+            }
+        } else if !self.is_truthy(&left) {
+            return Ok(left);
+        }
+
+        self.evaluate(&expr.right)
+    }
+
+    fn visit_set_expr(&mut self, expr: &crate::expr::SetExpr) -> Result<Value, crate::error::LoxError> {
+        let Value::Instance(instance) = self.evaluate(&expr.object)? else {
+            return Err(LoxError::new_with_token(
+                ErrorCode::NotAnInstance,
+                expr.name.clone(),
+                "Only instances have fields.".to_string(),
+            ));
+        };
+
+        let value = self.evaluate(&expr.value)?;
+        instance.set(&expr.name, value.clone());
+        Ok(value)
+    }
+
+    fn visit_super_expr(&mut self, expr: &crate::expr::SuperExpr) -> Result<Value, crate::error::LoxError> {
+        let distance = self.locals.get(&expr.id).copied().unwrap_or(0);
+        let superclass = self.environment.borrow().get_at(distance, &expr.keyword)?;
+        // `this` always lives in the scope just inside the one holding `super`.
+        let this = Token::new_with_span(
+            TokenType::This,
+            "this",
+            None,
+            expr.keyword.get_line(),
+            expr.keyword.get_column(),
+            expr.keyword.get_span(),
+        );
+        let object = self
+            .environment
+            .borrow()
+            .get_at(distance.saturating_sub(1), &this)?;
+
+        let (Value::Class(superclass), Value::Instance(object)) = (superclass, object) else {
+            return Err(LoxError::new_with_token(
+                ErrorCode::InvalidSuper,
+                expr.keyword.clone(),
+                "Can't use 'super' outside of a subclass method.".to_string(),
+            ));
+        };
+
+        match superclass.find_method(expr.method.get_lexeme()) {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(object)))),
+            None => Err(LoxError::new_with_token(
+                ErrorCode::UndefinedProperty,
+                expr.method.clone(),
+                format!("Undefined property '{}'.", expr.method.get_lexeme()),
+            )),
+        }
     }
 
-    fn visit_literal_expr(&self, expr: &crate::expr::LiteralExpr) -> Result<Literal, crate::error::LoxError> {
-        Ok(expr.value.clone().unwrap())
+    fn visit_this_expr(&mut self, expr: &crate::expr::ThisExpr) -> Result<Value, crate::error::LoxError> {
+        self.look_up_variable(&expr.keyword, expr.id)
     }
 
-    fn visit_unary_expr(&self, expr: &crate::expr::UnaryExpr) -> Result<Literal, crate::error::LoxError> {
+    fn visit_unary_expr(&mut self, expr: &crate::expr::UnaryExpr) -> Result<Value, crate::error::LoxError> {
         let right = self.evaluate(&expr.right)?;
 
         match expr.operator.get_token_type() {
             TokenType::Minus => match right {
-                Literal::Number(n) => return Ok(Literal::Number(-n)),
-                _ => return Ok(Literal::Nil),
+                Value::Number(n) => Ok(Value::Number(-n)),
+                _ => Err(LoxError::new_with_token(
+                    ErrorCode::OperandType,
+                    expr.operator.clone(),
+                    "Operand must be a number.".to_string(),
+                )
+                .with_secondary(expr.right.span(), right.type_name())),
             },
-            TokenType::Bang => Ok(Literal::Bool(!self.is_truthy(&right))),
-            _ => Err(LoxError::new_with_token(expr.operator.clone(), "Unreachable according to Nystrom".to_string(),))
+            TokenType::Bang => Ok(Value::Bool(!self.is_truthy(&right))),
+            _ => unreachable!("the parser only builds unary expressions from '!' and '-'"),
         }
     }
+
+    fn visit_variable_expr(&mut self, expr: &crate::expr::VariableExpr) -> Result<Value, crate::error::LoxError> {
+        self.look_up_variable(&expr.name, expr.id)
+    }
 }
 
 impl Interpreter {
-    fn evaluate(&self, expr: &Expr) -> Result<Literal, LoxError> {
-        expr.accept(self)
+    pub fn new() -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        for native in natives() {
+            globals
+                .borrow_mut()
+                .define(native.name, Value::Native(Rc::new(native)));
+        }
+
+        Self {
+            globals: Rc::clone(&globals),
+            environment: globals,
+            locals: HashMap::new(),
+        }
+    }
+
+    /// Records that the variable expression `id` refers to a local `depth` scopes out.
+    pub fn resolve(&mut self, id: usize, depth: usize) {
+        self.locals.insert(id, depth);
+    }
+
+    fn look_up_variable(&self, name: &Token, id: usize) -> Result<Value, LoxError> {
+        match self.locals.get(&id) {
+            Some(distance) => self.environment.borrow().get_at(*distance, name),
+            None => self.globals.borrow().get(name),
+        }
     }
 
-    fn is_truthy(&self, literal: &Literal) -> bool {
-        !matches!(literal, Literal::Nil | Literal::Bool(false))
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, LoxError> {
+        expr.accept_mut(self)
     }
 
-    pub fn interpret(&self, expr: &Expr) -> bool {
-        match self.evaluate(&expr) {
-            Ok(v) => {
-                println!("{}", v);
-                true
+    fn execute(&mut self, stmt: &Stmt) -> Result<Flow, LoxError> {
+        stmt.accept_mut(self)
+    }
+
+    pub fn execute_block(&mut self, statements: &[Stmt], environment: Environment) -> Result<Flow, LoxError> {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+
+        // Restore the outer scope before propagating an error or a return out of the block.
+        let mut result = Ok(Flow::Normal);
+        for statement in statements {
+            result = self.execute(statement);
+            if !matches!(result, Ok(Flow::Normal)) {
+                break;
             }
-            Err(e) => {
-                false
+        }
+
+        self.environment = previous;
+        result
+    }
+
+    fn check_arity(&self, paren: &Token, arity: usize, count: usize) -> Result<(), LoxError> {
+        if arity != count {
+            return Err(LoxError::new_with_token(
+                ErrorCode::ArityMismatch,
+                paren.clone(),
+                format!("Expected {arity} arguments but got {count}."),
+            ));
+        }
+        Ok(())
+    }
+
+    fn number_operands(&self, expr: &BinaryExpr, left: &Value, right: &Value) -> Result<(f64, f64), LoxError> {
+        match (left, right) {
+            (Value::Number(left), Value::Number(right)) => Ok((*left, *right)),
+            _ => Err(Interpreter::operand_error(expr, left, right, "Operands must be numbers.")),
+        }
+    }
+
+    /// Points at the operator and labels each operand with its type.
+    fn operand_error(expr: &BinaryExpr, left: &Value, right: &Value, message: &str) -> LoxError {
+        LoxError::new_with_token(ErrorCode::OperandType, expr.operator.clone(), message.to_string())
+            .with_secondary(expr.left.span(), left.type_name())
+            .with_secondary(expr.right.span(), right.type_name())
+    }
+
+    fn is_truthy(&self, value: &Value) -> bool {
+        !matches!(value, Value::Nil | Value::Bool(false))
+    }
+
+    /// Runs a program, stopping at the first runtime error.
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), LoxError> {
+        for statement in statements {
+            if let Flow::Return(_) = self.execute(statement)? {
+                break;
             }
         }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{token::*, expr::{LiteralExpr, UnaryExpr, BinaryExpr}};
+    use crate::{token::*, expr::{LiteralExpr, UnaryExpr, BinaryExpr}, parser::Parser, resolver::Resolver, scanner::Scanner};
+
+    fn run(terp: &mut Interpreter, source: &str) -> bool {
+        let (statements, _) = Parser::new(Scanner::new(source)).parse();
+        Resolver::new(terp).resolve(&statements).unwrap();
+        terp.interpret(&statements).is_ok()
+    }
+
+    fn global(terp: &Interpreter, name: &str) -> Result<Value, LoxError> {
+        let token = Token::new(TokenType::Identifier, name, None, 1);
+        terp.environment.borrow().get(&token)
+    }
 
     fn make_literal(o: Literal) -> Box<Expr> {
-        Box::new(Expr::Literal(LiteralExpr { value: Some(o) }))
+        Box::new(Expr::Literal(LiteralExpr { value: Some(o), span: Span::default() }))
     }
 
     fn make_literal_string(s: &str) -> Box<Expr> {
@@ -125,26 +485,189 @@ mod tests {
 
     #[test]
     fn test_unary_minus() {
-        let terp = Interpreter {};
+        let mut terp = Interpreter::new();
         let unary_expr = UnaryExpr {
-            operator: Token::new(TokenType::Minus, "-".to_string(), None, 123),
+            operator: Token::new(TokenType::Minus, "-", None, 123),
             right: make_literal(Literal::Number(123.0)),
+            span: Span::default(),
         };
         let result = terp.visit_unary_expr(&unary_expr);
         assert!(result.is_ok());
-        assert_eq!(result.ok(), Some(Literal::Number(-123.0)));
+        assert_eq!(result.ok(), Some(Value::Number(-123.0)));
     }
 
     #[test]
     fn test_equals_string() {
-        let terp = Interpreter {};
+        let mut terp = Interpreter::new();
         let binary_expr = BinaryExpr {
             left: make_literal_string("world"),
-            operator: Token::new(TokenType::Equal, "==".to_string(), None, 123),
+            operator: Token::new(TokenType::EqualEqual, "==", None, 123),
             right: make_literal_string("world"),
+            span: Span::default(),
         };
         let result = terp.visit_binary_expr(&binary_expr);
         assert!(result.is_ok());
-        assert_eq!(result.ok(), Some(Literal::Bool(true)));
+        assert_eq!(result.ok(), Some(Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_block_shadowing() {
+        let mut terp = Interpreter::new();
+        assert!(run(&mut terp, "var a = 1; var b = 1; { var a = 2; a = 3; b = a; }"));
+        assert_eq!(global(&terp, "a").ok(), Some(Value::Number(1.0)));
+        assert_eq!(global(&terp, "b").ok(), Some(Value::Number(3.0)));
+    }
+
+    #[test]
+    fn test_block_restores_environment_on_error() {
+        let mut terp = Interpreter::new();
+        assert!(!run(&mut terp, "{ var inner = 1; missing; }"));
+        assert!(global(&terp, "inner").is_err());
+    }
+
+    #[test]
+    fn test_logical_returns_operand() {
+        let mut terp = Interpreter::new();
+        assert!(run(&mut terp, "var a = nil or \"yes\"; var b = 0 and false; var c = nil and missing;"));
+        assert_eq!(global(&terp, "a").ok(), Some(Value::String("yes".into())));
+        assert_eq!(global(&terp, "b").ok(), Some(Value::Bool(false)));
+        assert_eq!(global(&terp, "c").ok(), Some(Value::Nil));
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let mut terp = Interpreter::new();
+        let source = "var sum = 0; for (var i = 0; i < 5; i = i + 1) { if (i == 2) sum = sum + 10; else sum = sum + i; } var n = 0; while (n < 3) n = n + 1;";
+        assert!(run(&mut terp, source));
+        assert_eq!(global(&terp, "sum").ok(), Some(Value::Number(18.0)));
+        assert_eq!(global(&terp, "n").ok(), Some(Value::Number(3.0)));
+    }
+
+    #[test]
+    fn test_closures_and_return() {
+        let mut terp = Interpreter::new();
+        let source = "fun makeCounter() { var i = 0; fun count() { i = i + 1; return i; } return count; }
+            var counter = makeCounter(); counter(); var second = counter();
+            fun fib(n) { if (n < 2) return n; return fib(n - 2) + fib(n - 1); } var f = fib(10);
+            fun find() { for (var i = 0; i < 10; i = i + 1) { if (i == 4) return i; } } var found = find();
+            fun noop() {} var nothing = noop();";
+        assert!(run(&mut terp, source));
+        assert_eq!(global(&terp, "second").ok(), Some(Value::Number(2.0)));
+        assert_eq!(global(&terp, "f").ok(), Some(Value::Number(55.0)));
+        assert_eq!(global(&terp, "found").ok(), Some(Value::Number(4.0)));
+        assert_eq!(global(&terp, "nothing").ok(), Some(Value::Nil));
+    }
+
+    #[test]
+    fn test_arity_mismatch() {
+        let mut terp = Interpreter::new();
+        let (statements, _) = Parser::new(Scanner::new("fun f(a, b) {}\nf(1, 2,\n 3);")).parse();
+        Resolver::new(&mut terp).resolve(&statements).unwrap();
+        terp.execute(&statements[0]).ok().unwrap();
+        let err = terp.execute(&statements[1]).err().unwrap();
+        assert_eq!(err.message, "Expected 2 arguments but got 3.");
+        assert_eq!(err.line, 3);
+    }
+
+    #[test]
+    fn test_classes() {
+        let mut terp = Interpreter::new();
+        let source = "class A { init(n) { this.n = n; return; } get() { return this.n; } }
+            class B < A { get() { return super.get() * 10; } }
+            var b = B(4); var value = b.get(); var method = b.get; b.n = 5; var rebound = method();
+            var same = b.init(7); same.n = 8;";
+        assert!(run(&mut terp, source));
+        assert_eq!(global(&terp, "value").ok(), Some(Value::Number(40.0)));
+        assert_eq!(global(&terp, "rebound").ok(), Some(Value::Number(50.0)));
+        assert_eq!(global(&terp, "same").ok(), global(&terp, "b").ok());
+        assert!(run(&mut terp, "var eight = b.get();"));
+        assert_eq!(global(&terp, "eight").ok(), Some(Value::Number(80.0)));
+    }
+
+    #[test]
+    fn test_class_errors() {
+        let mut terp = Interpreter::new();
+        assert!(!run(&mut terp, "var NotAClass = 1; class A < NotAClass {}"));
+        assert!(!run(&mut terp, "class C {} C().missing;"));
+        assert!(!run(&mut terp, "var n = 1; n.field = 2;"));
+    }
+
+    #[test]
+    fn test_resolved_closure_binding() {
+        let mut terp = Interpreter::new();
+        let source = "var a = \"global\"; var first; var second;
+            { fun showA() { return a; } first = showA(); var a = \"block\"; second = showA(); }";
+        assert!(run(&mut terp, source));
+        assert_eq!(global(&terp, "first").ok(), global(&terp, "second").ok());
+        assert_eq!(global(&terp, "second").ok(), Some(Value::String("global".into())));
     }
-}
\ No newline at end of file
+
+    fn runtime_error(source: &str) -> LoxError {
+        let mut terp = Interpreter::new();
+        let (statements, _) = Parser::new(Scanner::new(source)).parse();
+        Resolver::new(&mut terp).resolve(&statements).unwrap();
+        statements
+            .iter()
+            .find_map(|statement| terp.execute(statement).err())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_runtime_type_errors() {
+        assert_eq!(runtime_error("1 - \"a\";").message, "Operands must be numbers.");
+        assert_eq!(runtime_error("nil < 1;").message, "Operands must be numbers.");
+        assert_eq!(
+            runtime_error("true + nil;").message,
+            "Operands must be two numbers or two strings."
+        );
+        assert_eq!(runtime_error("-\"a\";").message, "Operand must be a number.");
+    }
+
+    #[test]
+    fn test_unicode_program() {
+        let mut terp = Interpreter::new();
+        assert!(run(&mut terp, "var 名前 = \"世界\"; var cafe\u{301} = \"👋 \" + 名前;"));
+        assert_eq!(global(&terp, "cafe\u{301}").ok(), Some(Value::String(Rc::from("👋 世界"))));
+        // The precomposed "é" is a different identifier from the decomposed one.
+        assert!(global(&terp, "café").is_err());
+    }
+
+    #[test]
+    fn test_runtime_error_location() {
+        let error = runtime_error("var a = 1;\nprint a +  nil;");
+        assert_eq!((error.line, error.column), (2, 9));
+        assert_eq!(error.span, Some(Span::new(19, 20)));
+    }
+
+    #[test]
+    fn test_equality_semantics() {
+        let mut terp = Interpreter::new();
+        let source = "class A {} var a = A(); var same = a == a; var other = a == A();
+            var mixed = 1 == \"1\"; var nils = nil == nil; var f = clock == clock;";
+        assert!(run(&mut terp, source));
+        assert_eq!(global(&terp, "same").ok(), Some(Value::Bool(true)));
+        assert_eq!(global(&terp, "other").ok(), Some(Value::Bool(false)));
+        assert_eq!(global(&terp, "mixed").ok(), Some(Value::Bool(false)));
+        assert_eq!(global(&terp, "nils").ok(), Some(Value::Bool(true)));
+        assert_eq!(global(&terp, "f").ok(), Some(Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let mut terp = Interpreter::new();
+        let source = r#"class Box {} var n = 3; var b = Box();
+            var s = "count: ${n} items, ${n > 2} ${nil} ${b} ${"in${"ner"}"}\${}";"#;
+        assert!(run(&mut terp, source));
+        assert_eq!(
+            global(&terp, "s").ok(),
+            Some(Value::String(Rc::from("count: 3 items, true nil Box instance inner${}")))
+        );
+    }
+
+    #[test]
+    fn test_interpolation_error_location() {
+        let error = runtime_error("print \"a ${1 + nil} b\";");
+        assert_eq!((error.line, error.column), (1, 14));
+        assert_eq!(error.span, Some(Span::new(13, 14)));
+    }
+}
@@ -0,0 +1,72 @@
+use core::fmt;
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    callable::LoxCallable,
+    error::LoxError,
+    interpreter::Interpreter,
+    lox_function::LoxFunction,
+    lox_instance::LoxInstance,
+    value::Value,
+};
+
+pub struct LoxClass {
+    name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<String, Rc<LoxFunction>>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self
+                .superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.find_method("init")
+            .map(|initializer| initializer.arity())
+            .unwrap_or(0)
+    }
+
+    /// Calling a class creates an instance and runs `init` on it, if there is one.
+    pub fn instantiate(
+        klass: &Rc<LoxClass>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, LoxError> {
+        let instance = Rc::new(LoxInstance::new(Rc::clone(klass)));
+        if let Some(initializer) = klass.find_method("init") {
+            initializer
+                .bind(Rc::clone(&instance))
+                .call(interpreter, arguments)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl fmt::Display for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
@@ -0,0 +1,84 @@
+use core::fmt;
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    callable::LoxCallable,
+    environment::Environment,
+    error::LoxError,
+    interpreter::{Flow, Interpreter},
+    lox_instance::LoxInstance,
+    stmt::{FunctionStmt, Stmt},
+    token::{Token, TokenType},
+    value::Value,
+};
+
+pub struct LoxFunction {
+    name: Token,
+    params: Rc<Vec<Token>>,
+    body: Rc<Vec<Stmt>>,
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: &FunctionStmt,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            name: declaration.name.clone(),
+            params: Rc::clone(&declaration.params),
+            body: Rc::clone(&declaration.body),
+            closure,
+            is_initializer,
+        }
+    }
+
+    /// Returns a copy of this method whose closure has `this` bound to `instance`.
+    pub fn bind(&self, instance: Rc<LoxInstance>) -> LoxFunction {
+        let mut environment = Environment::new_with_enclosing(Rc::clone(&self.closure));
+        environment.define("this", Value::Instance(instance));
+        LoxFunction {
+            name: self.name.clone(),
+            params: Rc::clone(&self.params),
+            body: Rc::clone(&self.body),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+
+    fn this(&self) -> Result<Value, LoxError> {
+        let this = Token::new(TokenType::This, "this", None, self.name.get_line());
+        self.closure.borrow().get_at(0, &this)
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, LoxError> {
+        let mut environment = Environment::new_with_enclosing(Rc::clone(&self.closure));
+        for (param, argument) in self.params.iter().zip(arguments) {
+            environment.define(param.get_lexeme(), argument);
+        }
+
+        let result = interpreter.execute_block(&self.body, environment)?;
+        if self.is_initializer {
+            return self.this();
+        }
+
+        match result {
+            Flow::Return(value) => Ok(value),
+            Flow::Normal => Ok(Value::Nil),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        self.params.len()
+    }
+}
+
+impl fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name.get_lexeme())
+    }
+}
@@ -0,0 +1,46 @@
+use core::fmt;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{error::LoxError, error_code::ErrorCode, lox_class::LoxClass, token::Token, value::Value};
+
+pub struct LoxInstance {
+    klass: Rc<LoxClass>,
+    fields: RefCell<HashMap<String, Value>>,
+}
+
+impl LoxInstance {
+    pub fn new(klass: Rc<LoxClass>) -> Self {
+        Self {
+            klass,
+            fields: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Looks up a field, falling back to a method bound to `instance`.
+    pub fn get(instance: &Rc<LoxInstance>, name: &Token) -> Result<Value, LoxError> {
+        if let Some(value) = instance.fields.borrow().get(name.get_lexeme()) {
+            return Ok(value.clone());
+        }
+
+        if let Some(method) = instance.klass.find_method(name.get_lexeme()) {
+            let bound = method.bind(Rc::clone(instance));
+            return Ok(Value::Function(Rc::new(bound)));
+        }
+
+        Err(LoxError::new_with_token(
+            ErrorCode::UndefinedProperty,
+            name.clone(),
+            format!("Undefined property '{}'.", name.get_lexeme()),
+        ))
+    }
+
+    pub fn set(&self, name: &Token, value: Value) {
+        self.fields.borrow_mut().insert(name.get_lexeme().to_string(), value);
+    }
+}
+
+impl fmt::Display for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.klass.name())
+    }
+}
@@ -1,77 +1,397 @@
 mod ast_printer;
+mod callable;
+mod cst;
+mod diagnostic;
+mod environment;
 mod error;
+mod error_code;
 mod expr;
+mod formatter;
 mod parser;
+mod resolver;
 mod scanner;
+mod stmt;
 mod token;
-mod util;
+mod unicode;
+mod value;
 mod interpreter;
+mod lox_class;
+mod lox_function;
+mod lox_instance;
+mod native_functions;
 
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 
 use ast_printer::AstPrinter;
 use parser::Parser;
 use scanner::Scanner;
 
-use crate::interpreter::Interpreter;
+use crate::{
+    cst::SyntaxTree,
+    diagnostic::{ErrorFormat, Renderer},
+    error::LoxError,
+    error_code::ErrorCode,
+    formatter::FormatError,
+    interpreter::Interpreter,
+    resolver::Resolver,
+};
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+// Exit codes from BSD sysexits.h, as used by the reference jlox.
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_NOINPUT: i32 = 66;
+const EX_SOFTWARE: i32 = 70;
+
+/// Exit code of `jlox fmt --check` when a file isn't formatted.
+const EX_UNFORMATTED: i32 = 1;
+
+const USAGE: &str = "Usage: jlox [--error-format=human|json] [--syntax-tree] [--print-ast] [script]
+       jlox fmt [--error-format=human|json] [--check] [--width=N] [file...]
+       jlox --explain <code>";
+
+/// Line width `jlox fmt` wraps code at unless given `--width`.
+const DEFAULT_WIDTH: usize = 80;
+
 fn main() {
-    let arg_len = std::env::args().len();
-    if arg_len > 2 {
-        panic!("Usage: jlox [script]");
-    } else if arg_len == 2 {
-        let file_name = std::env::args().nth(1).unwrap();
-        println!("{file_name}");
-        run_file(&file_name).expect("Failed to interpret");
+    let mut lox = Lox::new();
+    let mut script = None;
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if_eq("fmt").is_some() {
+        fmt(&mut lox, args);
+        return;
+    }
+    while let Some(arg) = args.next() {
+        if arg == "--explain" {
+            let Some(code) = args.next() else {
+                eprintln!("{USAGE}");
+                std::process::exit(EX_USAGE);
+            };
+            explain(&code);
+            return;
+        } else if let Some(format) = arg.strip_prefix("--error-format=") {
+            match format.parse() {
+                Ok(format) => lox.error_format = format,
+                Err(e) => {
+                    eprintln!("{e}\n{USAGE}");
+                    std::process::exit(EX_USAGE);
+                }
+            }
+        } else if arg == "--syntax-tree" {
+            lox.print_syntax_tree = true;
+        } else if arg == "--print-ast" {
+            lox.print_ast = true;
+        } else if script.is_none() && !arg.starts_with("--") {
+            script = Some(arg);
+        } else {
+            eprintln!("{USAGE}");
+            std::process::exit(EX_USAGE);
+        }
+    }
+
+    if let Some(file_name) = script {
+        if let Err(e) = lox.run_file(&file_name) {
+            eprintln!("Could not read '{file_name}': {e}");
+            std::process::exit(EX_NOINPUT);
+        }
+        if lox.had_error {
+            std::process::exit(EX_DATAERR);
+        }
+        if lox.had_runtime_error {
+            std::process::exit(EX_SOFTWARE);
+        }
     } else {
         println!("Prompt...");
-        run_prompt().expect("Failed to interpret");
+        lox.run_prompt().expect("Failed to interpret");
     }
 }
 
-pub fn run_file(file: &str) -> Result<()> {
-    let contents = std::fs::read_to_string(file)?;
-    run(contents.as_str())?;
-    Ok(())
+/// Prints the long description of an error code, as in `jlox --explain L0003`.
+fn explain(code: &str) {
+    match ErrorCode::from_code(code) {
+        Some(code) => println!("{code}: {}", code.explain()),
+        None => {
+            eprintln!("'{code}' is not a Lox error code.");
+            std::process::exit(EX_USAGE);
+        }
+    }
+}
+
+/// Formats each file in place, or stdin to stdout if there are none. With
+/// `--check`, lists the files that aren't formatted instead and fails if any
+/// are.
+fn fmt(lox: &mut Lox, args: impl Iterator<Item = String>) {
+    let mut check = false;
+    let mut width = DEFAULT_WIDTH;
+    let mut files = Vec::new();
+    for arg in args {
+        if arg == "--check" {
+            check = true;
+        } else if let Some(n) = arg.strip_prefix("--width=") {
+            match n.parse() {
+                Ok(n) if n > 0 => width = n,
+                _ => {
+                    eprintln!("Invalid width '{n}'.\n{USAGE}");
+                    std::process::exit(EX_USAGE);
+                }
+            }
+        } else if let Some(format) = arg.strip_prefix("--error-format=") {
+            match format.parse() {
+                Ok(format) => lox.error_format = format,
+                Err(e) => {
+                    eprintln!("{e}\n{USAGE}");
+                    std::process::exit(EX_USAGE);
+                }
+            }
+        } else if !arg.starts_with("--") {
+            files.push(arg);
+        } else {
+            eprintln!("{USAGE}");
+            std::process::exit(EX_USAGE);
+        }
+    }
+
+    let mut unformatted = false;
+    if files.is_empty() {
+        let mut source = String::new();
+        if let Err(e) = std::io::Read::read_to_string(&mut std::io::stdin(), &mut source) {
+            eprintln!("Could not read stdin: {e}");
+            std::process::exit(EX_NOINPUT);
+        }
+        match formatter::format(&source, width) {
+            Ok(formatted) if check => unformatted = formatted != source,
+            Ok(formatted) => print!("{formatted}"),
+            Err(e) => format_failed(lox, &source, e),
+        }
+    }
+    for file in &files {
+        let source = match std::fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Could not read '{file}': {e}");
+                std::process::exit(EX_NOINPUT);
+            }
+        };
+        lox.origin = file.clone();
+        match formatter::format(&source, width) {
+            Ok(formatted) if formatted == source => {}
+            Ok(_) if check => {
+                println!("{file}");
+                unformatted = true;
+            }
+            Ok(formatted) => {
+                if let Err(e) = std::fs::write(file, formatted) {
+                    eprintln!("Could not write '{file}': {e}");
+                    std::process::exit(EX_SOFTWARE);
+                }
+            }
+            Err(e) => format_failed(lox, &source, e),
+        }
+    }
+    if lox.had_error {
+        std::process::exit(EX_DATAERR);
+    }
+    if lox.had_runtime_error {
+        std::process::exit(EX_SOFTWARE);
+    }
+    if unformatted {
+        std::process::exit(EX_UNFORMATTED);
+    }
 }
 
-pub fn run_prompt() -> Result<()> {
-    let mut buf = String::new();
-    loop {
-        print!("> ");
-        std::io::stdout().flush().expect("Failed to flush");
-        std::io::stdin()
-            .read_line(&mut buf)
-            .expect("Failed to read line.");
-        if buf == "\n" {
-            break;
+/// Reports why `source` couldn't be formatted; it is left as it was.
+fn format_failed(lox: &mut Lox, source: &str, error: FormatError) {
+    match error {
+        FormatError::Syntax(errors) => {
+            lox.report(source, &errors);
+            lox.had_error = true;
+        }
+        FormatError::Changed => {
+            eprintln!(
+                "Could not format '{}': the result would change more than whitespace. \
+                 This is a bug in the formatter.",
+                lox.origin
+            );
+            lox.had_runtime_error = true;
+        }
+    }
+}
+
+/// Prints the lossless syntax tree of `source`, then each declaration's and
+/// each expression's node range next to the AST parsed from it.
+fn print_syntax_tree(source: &str) -> Result<()> {
+    let (tree, _) = SyntaxTree::parse(source);
+    println!("Syntax tree:");
+    print!("{}", tree.root.dump());
+    let printer = AstPrinter::new();
+    println!("Declarations:");
+    for (node, statement) in tree.declarations() {
+        let span = node.span();
+        println!("{:?}@{}..{} {}", node.kind(), span.start, span.end, printer.print_stmt(statement)?);
+    }
+    println!("Expressions:");
+    for node in tree.root.descendants() {
+        if let Some(expr) = tree.expr(&node) {
+            let span = node.span();
+            println!("{:?}@{}..{} {}", node.kind(), span.start, span.end, printer.print(expr)?);
         }
-        run(buf.as_str())?;
-        buf.clear();
     }
     Ok(())
 }
 
-pub fn run(line: &str) -> Result<()> {
-    let mut scanner = Scanner::new(line.to_string());
-    let tokens = scanner.scan_tokens()?;
-    tokens.iter().for_each(|t| {
-        println!("{}", t);
-    });
-    println!("\x1b[0;32mParsing...\x1b[0m");
-    let mut parser = Parser::new(tokens);
-    match parser.parse() {
-        None => {
-            println!("AST None");
+struct Lox {
+    interpreter: Interpreter,
+    /// Names the source being run in error messages.
+    origin: String,
+    /// Everything run so far. Functions keep the spans of the REPL line they
+    /// were defined on, so errors are rendered against every line, not the last.
+    source: String,
+    /// Whether errors are rendered with ANSI colors.
+    color: bool,
+    error_format: ErrorFormat,
+    /// Whether to show the lossless syntax tree of each program before running it.
+    print_syntax_tree: bool,
+    /// Whether to show the AST of each program before running it.
+    print_ast: bool,
+    /// Set when scanning, parsing or resolving reports an error.
+    had_error: bool,
+    /// Set when the interpreter hits an error while running the program.
+    had_runtime_error: bool,
+}
+
+impl Lox {
+    fn new() -> Self {
+        Self {
+            interpreter: Interpreter::new(),
+            origin: "<stdin>".to_string(),
+            source: String::new(),
+            color: std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            error_format: ErrorFormat::Human,
+            print_syntax_tree: false,
+            print_ast: false,
+            had_error: false,
+            had_runtime_error: false,
+        }
+    }
+
+    fn run_file(&mut self, file: &str) -> Result<()> {
+        let contents = std::fs::read_to_string(file)?;
+        self.origin = file.to_string();
+        self.run(contents.as_str())?;
+        Ok(())
+    }
+
+    fn run_prompt(&mut self) -> Result<()> {
+        let mut buf = String::new();
+        loop {
+            print!("> ");
+            std::io::stdout().flush().expect("Failed to flush");
+            std::io::stdin()
+                .read_line(&mut buf)
+                .expect("Failed to read line.");
+            if buf == "\n" {
+                break;
+            }
+            self.run(buf.as_str())?;
+            // A mistake on one line shouldn't end the whole session.
+            self.had_error = false;
+            self.had_runtime_error = false;
+            buf.clear();
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, line: &str) -> Result<()> {
+        if self.print_syntax_tree {
+            print_syntax_tree(line)?;
+        }
+        if !self.source.is_empty() && !self.source.ends_with('\n') {
+            self.source.push('\n');
         }
-        Some(expr) => {
+        let start = self.source.len();
+        self.source.push_str(line);
+        // The parser pulls tokens from the scanner as it goes and reports lexical
+        // errors alongside syntax errors.
+        let mut parser = Parser::new(Scanner::starting_at(&self.source, start));
+        let (statements, errors) = parser.parse();
+        if !errors.is_empty() {
+            self.report(&self.source, &errors);
+            self.had_error = true;
+            return Ok(());
+        }
+        if self.print_ast {
             let printer = AstPrinter::new();
-            println!("AST Printer:\n{}", printer.print(&expr)?);
-            let interpreter = Interpreter {};
-            interpreter.interpret(&expr);
+            println!("AST Printer:");
+            for statement in &statements {
+                println!("{}", printer.print_stmt(statement)?);
+            }
+        }
+        let mut resolver = Resolver::new(&mut self.interpreter);
+        if let Err(errors) = resolver.resolve(&statements) {
+            self.report(&self.source, &errors);
+            self.had_error = true;
+            return Ok(());
         }
+        if let Err(error) = self.interpreter.interpret(&statements) {
+            self.report(&self.source, &[error]);
+            self.had_runtime_error = true;
+        }
+        Ok(())
+    }
+
+    fn report(&self, source: &str, errors: &[LoxError]) {
+        let renderer = Renderer::new(source, &self.origin, self.color);
+        for error in errors {
+            match self.error_format {
+                ErrorFormat::Human => eprintln!("{}", renderer.render(error)),
+                ErrorFormat::Json => eprintln!("{}", renderer.render_json(error)),
+            }
+        }
+        if let (ErrorFormat::Human, Some(error)) = (self.error_format, errors.first()) {
+            eprintln!("For more information about an error, try `jlox --explain {}`.", error.code);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_flags() {
+        let mut lox = Lox::new();
+        lox.run("print 1 +;").unwrap();
+        assert!(lox.had_error);
+        assert!(!lox.had_runtime_error);
+
+        let mut lox = Lox::new();
+        lox.run("print nil - 1;").unwrap();
+        assert!(!lox.had_error);
+        assert!(lox.had_runtime_error);
+
+        let mut lox = Lox::new();
+        lox.run("var a = 1; print a;").unwrap();
+        assert!(!lox.had_error && !lox.had_runtime_error);
+    }
+
+    #[test]
+    fn test_error_in_function_from_earlier_line() {
+        // As in the REPL: `f` keeps the spans of the first line, and its error
+        // must be rendered against that line rather than the one calling it.
+        let mut lox = Lox::new();
+        lox.run("fun f() { return nil - 1; }\n").unwrap();
+        lox.run("print \"éééééééééééé\" + f();\n").unwrap();
+        assert!(lox.had_runtime_error);
+        assert_eq!(
+            lox.source,
+            "fun f() { return nil - 1; }\nprint \"éééééééééééé\" + f();\n"
+        );
+
+        let mut lox = Lox::new();
+        lox.run("var a = 1;").unwrap();
+        lox.run("print a;").unwrap();
+        assert_eq!(lox.source, "var a = 1;\nprint a;");
+        assert!(!lox.had_error && !lox.had_runtime_error);
     }
-    Ok(())
 }
@@ -0,0 +1,40 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    callable::LoxCallable, error::LoxError, error_code::ErrorCode, interpreter::Interpreter,
+    value::Value,
+};
+
+/// A function implemented in Rust and exposed to Lox code as a global.
+pub struct NativeFunction {
+    pub name: &'static str,
+    arity: usize,
+    function: fn(&mut Interpreter, &[Value]) -> Result<Value, LoxError>,
+}
+
+impl LoxCallable for NativeFunction {
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, LoxError> {
+        (self.function)(interpreter, &arguments)
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+}
+
+/// Every native function, in the order they are defined as globals.
+pub fn natives() -> Vec<NativeFunction> {
+    vec![NativeFunction {
+        name: "clock",
+        arity: 0,
+        function: clock,
+    }]
+}
+
+/// `clock()`: seconds since the Unix epoch, for benchmarking Lox code.
+fn clock(_interpreter: &mut Interpreter, _arguments: &[Value]) -> Result<Value, LoxError> {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => Ok(Value::Number(duration.as_secs_f64())),
+        Err(e) => Err(LoxError::new(ErrorCode::NativeFunction, 0, "".to_string(), e.to_string())),
+    }
+}
@@ -1,49 +1,552 @@
-// expression     → literal
-//                | unary
-//                | binary
-//                | grouping ;
-
-// literal        → NUMBER | STRING | "true" | "false" | "nil" ;
-// grouping       → "(" expression ")" ;
-// unary          → ( "-" | "!" ) expression ;
-// binary         → expression operator expression ;
-// operator       → "==" | "!=" | "<" | "<=" | ">" | ">="
-//                | "+"  | "-"  | "*" | "/" ;
+// program        → declaration* EOF ;
+
+// declaration    → classDecl
+//                | funDecl
+//                | varDecl
+//                | statement ;
+
+// classDecl      → "class" IDENTIFIER ( "<" IDENTIFIER )?
+//                  "{" function* "}" ;
+// funDecl        → "fun" function ;
+// function       → IDENTIFIER "(" parameters? ")" block ;
+// parameters     → IDENTIFIER ( "," IDENTIFIER )* ;
+// varDecl        → "var" IDENTIFIER ( "=" expression )? ";" ;
+
+// statement      → exprStmt
+//                | forStmt
+//                | ifStmt
+//                | printStmt
+//                | returnStmt
+//                | whileStmt
+//                | block ;
+
+// forStmt        → "for" "(" ( varDecl | exprStmt | ";" )
+//                  expression? ";"
+//                  expression? ")" statement ;
+// ifStmt         → "if" "(" expression ")" statement
+//                  ( "else" statement )? ;
+// returnStmt     → "return" expression? ";" ;
+// whileStmt      → "while" "(" expression ")" statement ;
+
+// block          → "{" declaration* "}" ;
+
+// exprStmt       → expression ";" ;
+// printStmt      → "print" expression ";" ;
+
+// expression     → assignment ;
+// assignment     → ( call "." )? IDENTIFIER "=" assignment
+//                | logic_or ;
+// logic_or       → logic_and ( "or" logic_and )* ;
+// logic_and      → equality ( "and" equality )* ;
+// equality       → comparison ( ( "!=" | "==" ) comparison )* ;
+// comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+// term           → factor ( ( "-" | "+" ) factor )* ;
+// factor         → unary ( ( "/" | "*" ) unary )* ;
+// unary          → ( "!" | "-" ) unary | call ;
+// call           → primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
+// arguments      → expression ( "," expression )* ;
+// primary        → NUMBER | STRING | "true" | "false" | "nil"
+//                | "this" | IDENTIFIER | "super" "." IDENTIFIER
+//                | interpolation | "(" expression ")" ;
+// interpolation  → INTERPOLATION expression
+//                  ( INTERPOLATION_MIDDLE expression )* INTERPOLATION_END ;
+
+use std::{
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use crate::{
+    cst::{Checkpoint, GreenNode, SyntaxBuilder, SyntaxKind},
     error::LoxError,
+    error_code::ErrorCode,
     expr::{Expr, *},
-    token::{Literal, Token, TokenType},
+    stmt::*,
+    scanner::Scanner,
+    token::{Literal, Span, Token, TokenType},
 };
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+/// Source of the ids the resolver uses to tell variable references apart. It is
+/// global so ids stay unique across every line typed into the REPL.
+static NEXT_EXPR_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Recursive descent parser that pulls tokens from a stream, such as a
+/// `Scanner`, one at a time. Only the current and previous tokens are kept.
+pub struct Parser<I> {
+    tokens: I,
+    current: Token,
+    previous: Option<Token>,
+    errors: Vec<LoxError>,
+    // Set after a lexical error, to hold back syntax errors it is likely to have
+    // caused until the parser has synchronized.
+    panic_mode: bool,
+    // Records every token consumed, and trivia, when building a syntax tree.
+    syntax: Option<SyntaxBuilder>,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
-        Parser { tokens, current: 0 }
+impl Parser<Scanner> {
+    /// A parser that also builds a lossless syntax tree of `source`, for
+    /// `finish_syntax` to return once parsing is done.
+    pub fn new_lossless(source: &str) -> Self {
+        Parser::with_syntax(Scanner::with_trivia(source), Some(SyntaxBuilder::new(source)))
+    }
+}
+
+impl<I: Iterator<Item = Result<Token, LoxError>>> Parser<I> {
+    pub fn new(tokens: I) -> Self {
+        Parser::with_syntax(tokens, None)
+    }
+
+    fn with_syntax(tokens: I, syntax: Option<SyntaxBuilder>) -> Self {
+        let mut parser = Parser {
+            tokens,
+            current: Token::new(TokenType::Eof, "", None, 0),
+            previous: None,
+            errors: Vec::new(),
+            panic_mode: false,
+            syntax,
+        };
+        parser.current = parser.next_token();
+        parser
     }
 
-    pub fn parse(&mut self) -> Option<Expr> {
-        match self.expression() {
-            Ok(expr) => Some(expr),
-            Err(_) => None,
+    /// Parses the whole program. Declarations that fail to parse are skipped after
+    /// recording their error, so the result is every statement that did parse along
+    /// with every syntax error in the source.
+    pub fn parse(&mut self) -> (Vec<Stmt>, Vec<LoxError>) {
+        let mut statements = Vec::new();
+        while !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
         }
+        (statements, std::mem::take(&mut self.errors))
+    }
+
+    /// The syntax tree of everything parsed, if the parser was built with
+    /// `new_lossless`.
+    pub fn finish_syntax(&mut self) -> Option<GreenNode> {
+        self.syntax.take().map(SyntaxBuilder::finish)
+    }
+
+    fn declaration(&mut self) -> Option<Stmt> {
+        let checkpoint = self.checkpoint();
+        let statement = match self.parse_declaration() {
+            Ok(statement) => Some(statement),
+            Err(e) => {
+                if !self.panic_mode {
+                    self.errors.push(e);
+                }
+                self.synchronize();
+                self.wrap(checkpoint, SyntaxKind::Error);
+                None
+            }
+        };
+        self.panic_mode = false;
+        statement
+    }
+
+    fn parse_declaration(&mut self) -> Result<Stmt, LoxError> {
+        let checkpoint = self.checkpoint();
+        let doc = self.doc_comment();
+        let (kind, statement) = if self.is_match(&[TokenType::Class]) {
+            (SyntaxKind::Class, self.class_declaration(doc)?)
+        } else if self.is_match(&[TokenType::Fun]) {
+            (SyntaxKind::Function, Stmt::Function(self.function("function", doc)?))
+        } else if self.is_match(&[TokenType::Var]) {
+            (SyntaxKind::Var, self.var_declaration(doc)?)
+        } else {
+            return self.statement();
+        };
+        self.wrap(checkpoint, kind);
+        Ok(statement)
+    }
+
+    /// The `///` comments before the next token, which document the declaration
+    /// it starts. Comments anywhere else are ignored.
+    fn doc_comment(&self) -> Option<String> {
+        self.peek().get_doc().map(str::to_string)
+    }
+
+    fn class_declaration(&mut self, doc: Option<String>) -> Result<Stmt, LoxError> {
+        let name = self.consume(TokenType::Identifier, "Expect class name.".to_string())?;
+
+        let superclass = if self.is_match(&[TokenType::Less]) {
+            let name = self.consume(
+                TokenType::Identifier,
+                "Expect superclass name.".to_string(),
+            )?;
+            Some(Box::new(Expr::Variable(VariableExpr {
+                span: name.get_span(),
+                name,
+                id: Self::next_id(),
+            })))
+        } else {
+            None
+        };
+
+        self.consume(
+            TokenType::LeftBrace,
+            "Expect '{' before class body.".to_string(),
+        )?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let checkpoint = self.checkpoint();
+            let doc = self.doc_comment();
+            methods.push(self.function("method", doc)?);
+            self.wrap(checkpoint, SyntaxKind::Function);
+        }
+
+        self.consume(
+            TokenType::RightBrace,
+            "Expect '}' after class body.".to_string(),
+        )?;
+
+        Ok(Stmt::Class(ClassStmt {
+            name,
+            superclass,
+            methods,
+            doc,
+        }))
+    }
+
+    fn function(&mut self, kind: &str, doc: Option<String>) -> Result<FunctionStmt, LoxError> {
+        let name = self.consume(TokenType::Identifier, format!("Expect {kind} name."))?;
+
+        self.consume(
+            TokenType::LeftParen,
+            format!("Expect '(' after {kind} name."),
+        )?;
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= 255 {
+                    let error = Self::error(
+                        ErrorCode::TooManyArguments,
+                        self.peek(),
+                        "Can't have more than 255 parameters.".to_string(),
+                    );
+                    self.errors.push(error);
+                }
+                params.push(self.consume(
+                    TokenType::Identifier,
+                    "Expect parameter name.".to_string(),
+                )?);
+                if !self.is_match(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after parameters.".to_string(),
+        )?;
+
+        let checkpoint = self.checkpoint();
+        self.consume(
+            TokenType::LeftBrace,
+            format!("Expect '{{' before {kind} body."),
+        )?;
+        let body = self.block()?;
+        self.wrap(checkpoint, SyntaxKind::Block);
+
+        Ok(FunctionStmt {
+            name,
+            params: Rc::new(params),
+            body: Rc::new(body),
+            doc,
+        })
+    }
+
+    fn var_declaration(&mut self, doc: Option<String>) -> Result<Stmt, LoxError> {
+        let name = self.consume(TokenType::Identifier, "Expect variable name.".to_string())?;
+
+        let initializer = if self.is_match(&[TokenType::Equal]) {
+            Some(Box::new(self.expression()?))
+        } else {
+            None
+        };
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.".to_string(),
+        )?;
+        Ok(Stmt::Var(VarStmt { name, initializer, doc }))
+    }
+
+    fn statement(&mut self) -> Result<Stmt, LoxError> {
+        let checkpoint = self.checkpoint();
+        let (kind, statement) = if self.is_match(&[TokenType::For]) {
+            (SyntaxKind::For, self.for_statement()?)
+        } else if self.is_match(&[TokenType::If]) {
+            (SyntaxKind::If, self.if_statement()?)
+        } else if self.is_match(&[TokenType::Print]) {
+            (SyntaxKind::Print, self.print_statement()?)
+        } else if self.is_match(&[TokenType::Return]) {
+            (SyntaxKind::Return, self.return_statement()?)
+        } else if self.is_match(&[TokenType::While]) {
+            (SyntaxKind::While, self.while_statement()?)
+        } else if self.is_match(&[TokenType::LeftBrace]) {
+            let statements = self.block()?;
+            (SyntaxKind::Block, Stmt::Block(BlockStmt { statements }))
+        } else {
+            (SyntaxKind::Expression, self.expression_statement()?)
+        };
+        self.wrap(checkpoint, kind);
+        Ok(statement)
+    }
+
+    fn for_statement(&mut self) -> Result<Stmt, LoxError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.".to_string())?;
+
+        let checkpoint = self.checkpoint();
+        let initializer = if self.is_match(&[TokenType::Semicolon]) {
+            None
+        } else if self.is_match(&[TokenType::Var]) {
+            let initializer = self.var_declaration(None)?;
+            self.wrap(checkpoint, SyntaxKind::Var);
+            Some(initializer)
+        } else {
+            let initializer = self.expression_statement()?;
+            self.wrap(checkpoint, SyntaxKind::Expression);
+            Some(initializer)
+        };
+
+        let condition = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after loop condition.".to_string(),
+        )?;
+
+        let increment = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after for clauses.".to_string(),
+        )?;
+
+        // Desugar into `{ initializer; while (condition) { body; increment; } }`.
+        let mut body = self.statement()?;
+
+        if let Some(increment) = increment {
+            body = Stmt::Block(BlockStmt {
+                statements: vec![
+                    body,
+                    Stmt::Expression(ExpressionStmt {
+                        expression: Box::new(increment),
+                    }),
+                ],
+            });
+        }
+
+        // An omitted condition is an implicit `true` where the condition would have been.
+        let condition = condition.unwrap_or(Expr::Literal(LiteralExpr {
+            value: Some(Literal::Bool(true)),
+            span: Span::new(semicolon.get_span().start, semicolon.get_span().start),
+        }));
+        body = Stmt::While(WhileStmt {
+            condition: Box::new(condition),
+            body: Box::new(body),
+        });
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(BlockStmt {
+                statements: vec![initializer, body],
+            });
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, LoxError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.".to_string())?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after if condition.".to_string(),
+        )?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.is_match(&[TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If(IfStmt {
+            condition: Box::new(condition),
+            then_branch,
+            else_branch,
+        }))
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, LoxError> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.".to_string())?;
+        Ok(Stmt::Print(PrintStmt {
+            expression: Box::new(value),
+        }))
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().unwrap().clone();
+        let value = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(Box::new(self.expression()?))
+        };
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after return value.".to_string(),
+        )?;
+        Ok(Stmt::Return(ReturnStmt { keyword, value }))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, LoxError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.".to_string())?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after condition.".to_string(),
+        )?;
+        let body = self.statement()?;
+
+        Ok(Stmt::While(WhileStmt {
+            condition: Box::new(condition),
+            body: Box::new(body),
+        }))
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, LoxError> {
+        let mut statements = Vec::new();
+
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after block.".to_string())?;
+        Ok(statements)
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt, LoxError> {
+        let expr = self.expression()?;
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after expression.".to_string(),
+        )?;
+        Ok(Stmt::Expression(ExpressionStmt {
+            expression: Box::new(expr),
+        }))
     }
 
     fn expression(&mut self) -> Result<Expr, LoxError> {
-        self.equality()
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> Result<Expr, LoxError> {
+        let checkpoint = self.checkpoint();
+        let expr = self.or()?;
+
+        if self.is_match(&[TokenType::Equal]) {
+            let equals = self.previous().unwrap().clone();
+            let value = self.assignment()?;
+            let kind = match expr {
+                Expr::Get(_) => SyntaxKind::Set,
+                _ => SyntaxKind::Assign,
+            };
+            self.wrap(checkpoint, kind);
+
+            return match expr {
+                Expr::Variable(expr) => Ok(Expr::Assign(AssignExpr {
+                    span: expr.span.to(value.span()),
+                    name: expr.name,
+                    value: Box::new(value),
+                    id: Self::next_id(),
+                })),
+                Expr::Get(expr) => Ok(Expr::Set(SetExpr {
+                    span: expr.span.to(value.span()),
+                    object: expr.object,
+                    name: expr.name,
+                    value: Box::new(value),
+                })),
+                expr => {
+                    // The parser isn't confused here, so report without synchronizing.
+                    self.errors.push(
+                        Self::error(
+                            ErrorCode::InvalidAssignmentTarget,
+                            &equals,
+                            "Invalid assignment target.".to_string(),
+                        )
+                            .with_secondary(expr.span(), "cannot be assigned to")
+                            .with_help("only variables and fields can be assigned to"),
+                    );
+                    Ok(expr)
+                }
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, LoxError> {
+        let checkpoint = self.checkpoint();
+        let mut expr = self.and()?;
+
+        while self.is_match(&[TokenType::Or]) {
+            let operator = self.previous().unwrap().clone();
+            let right = self.and()?;
+            self.wrap(checkpoint, SyntaxKind::Logical);
+            expr = Expr::Logical(LogicalExpr {
+                span: expr.span().to(right.span()),
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, LoxError> {
+        let checkpoint = self.checkpoint();
+        let mut expr = self.equality()?;
+
+        while self.is_match(&[TokenType::And]) {
+            let operator = self.previous().unwrap().clone();
+            let right = self.equality()?;
+            self.wrap(checkpoint, SyntaxKind::Logical);
+            expr = Expr::Logical(LogicalExpr {
+                span: expr.span().to(right.span()),
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
+        }
+
+        Ok(expr)
     }
 
     fn equality(&mut self) -> Result<Expr, LoxError> {
+        let checkpoint = self.checkpoint();
         let mut expr = self.comparison()?;
 
-        while self.is_match(&[TokenType::BangEqual, TokenType::Equal]) {
+        while self.is_match(&[TokenType::BangEqual, TokenType::EqualEqual]) {
             let operator = self.previous().unwrap().clone();
             let right = self.comparison()?;
+            self.wrap(checkpoint, SyntaxKind::Binary);
             expr = Expr::Binary(BinaryExpr {
+                span: expr.span().to(right.span()),
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
@@ -53,6 +556,7 @@ impl Parser {
     }
 
     fn comparison(&mut self) -> Result<Expr, LoxError> {
+        let checkpoint = self.checkpoint();
         let mut expr = self.term()?;
 
         while self.is_match(&[
@@ -63,7 +567,9 @@ impl Parser {
         ]) {
             let operator = self.previous().unwrap().clone();
             let right = self.term()?;
+            self.wrap(checkpoint, SyntaxKind::Binary);
             expr = Expr::Binary(BinaryExpr {
+                span: expr.span().to(right.span()),
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
@@ -73,12 +579,15 @@ impl Parser {
     }
 
     fn term(&mut self) -> Result<Expr, LoxError> {
+        let checkpoint = self.checkpoint();
         let mut expr = self.factor()?;
 
         while self.is_match(&[TokenType::Minus, TokenType::Plus]) {
             let operator = self.previous().unwrap().clone();
             let right = self.factor()?;
+            self.wrap(checkpoint, SyntaxKind::Binary);
             expr = Expr::Binary(BinaryExpr {
+                span: expr.span().to(right.span()),
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
@@ -89,12 +598,15 @@ impl Parser {
     }
 
     fn factor(&mut self) -> Result<Expr, LoxError> {
+        let checkpoint = self.checkpoint();
         let mut expr = self.unary()?;
 
         while self.is_match(&[TokenType::Slash, TokenType::Star]) {
             let operator = self.previous().unwrap().clone();
             let right = self.unary()?;
+            self.wrap(checkpoint, SyntaxKind::Binary);
             expr = Expr::Binary(BinaryExpr {
+                span: expr.span().to(right.span()),
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
@@ -105,64 +617,200 @@ impl Parser {
     }
 
     fn unary(&mut self) -> Result<Expr, LoxError> {
+        let checkpoint = self.checkpoint();
         if self.is_match(&[TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous().unwrap().clone();
             let right = self.unary()?;
+            self.wrap(checkpoint, SyntaxKind::Unary);
             return Ok(Expr::Unary(UnaryExpr {
+                span: operator.get_span().to(right.span()),
                 operator,
                 right: Box::new(right),
             }));
         }
 
-        Ok(self.primary()?)
+        self.call()
     }
 
-    fn primary(&mut self) -> Result<Expr, LoxError> {
-        if self.is_match(&[TokenType::False]) {
-            return Ok(Expr::Literal(LiteralExpr {
-                value: Some(Literal::Bool(false)),
-            }));
-        }
-        if self.is_match(&[TokenType::True]) {
-            return Ok(Expr::Literal(LiteralExpr {
-                value: Some(Literal::Bool(false)),
-            }));
-        }
-        if self.is_match(&[TokenType::Nil]) {
-            return Ok(Expr::Literal(LiteralExpr {
-                value: Some(Literal::Nil),
-            }));
+    fn call(&mut self) -> Result<Expr, LoxError> {
+        let checkpoint = self.checkpoint();
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.is_match(&[TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+                self.wrap(checkpoint, SyntaxKind::Call);
+            } else if self.is_match(&[TokenType::Dot]) {
+                let name = self.consume(
+                    TokenType::Identifier,
+                    "Expect property name after '.'.".to_string(),
+                )?;
+                self.wrap(checkpoint, SyntaxKind::Get);
+                expr = Expr::Get(GetExpr {
+                    span: expr.span().to(name.get_span()),
+                    object: Box::new(expr),
+                    name,
+                });
+            } else {
+                break;
+            }
         }
 
-        if self.is_match(&[TokenType::Number, TokenType::String]) {
-            return Ok(Expr::Literal(LiteralExpr {
-                value: self.previous().unwrap().get_literal().clone(),
-            }));
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, LoxError> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= 255 {
+                    let error = Self::error(
+                        ErrorCode::TooManyArguments,
+                        self.peek(),
+                        "Can't have more than 255 arguments.".to_string(),
+                    );
+                    self.errors.push(error);
+                }
+                arguments.push(self.expression()?);
+                if !self.is_match(&[TokenType::Comma]) {
+                    break;
+                }
+            }
         }
 
-        if self.is_match(&[TokenType::LeftParen]) {
+        let paren = self.consume(
+            TokenType::RightParen,
+            "Expect ')' after arguments.".to_string(),
+        )?;
+
+        Ok(Expr::Call(CallExpr {
+            span: callee.span().to(paren.get_span()),
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        }))
+    }
+
+    fn primary(&mut self) -> Result<Expr, LoxError> {
+        let checkpoint = self.checkpoint();
+        let (kind, expr) = if self.is_match(&[TokenType::False]) {
+            let expr = Expr::Literal(LiteralExpr {
+                value: Some(Literal::Bool(false)),
+                span: self.previous_span(),
+            });
+            (SyntaxKind::Literal, expr)
+        } else if self.is_match(&[TokenType::True]) {
+            let expr = Expr::Literal(LiteralExpr {
+                value: Some(Literal::Bool(true)),
+                span: self.previous_span(),
+            });
+            (SyntaxKind::Literal, expr)
+        } else if self.is_match(&[TokenType::Nil]) {
+            let expr = Expr::Literal(LiteralExpr {
+                value: Some(Literal::Nil),
+                span: self.previous_span(),
+            });
+            (SyntaxKind::Literal, expr)
+        } else if self.is_match(&[TokenType::Number, TokenType::String]) {
+            let expr = Expr::Literal(LiteralExpr {
+                value: self.previous().unwrap().get_literal().clone(),
+                span: self.previous_span(),
+            });
+            (SyntaxKind::Literal, expr)
+        } else if self.is_match(&[TokenType::Interpolation]) {
+            (SyntaxKind::Interpolation, self.interpolation()?)
+        } else if self.is_match(&[TokenType::Super]) {
+            let keyword = self.previous().unwrap().clone();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.".to_string())?;
+            let method = self.consume(
+                TokenType::Identifier,
+                "Expect superclass method name.".to_string(),
+            )?;
+            let expr = Expr::Super(SuperExpr {
+                span: keyword.get_span().to(method.get_span()),
+                keyword,
+                method,
+                id: Self::next_id(),
+            });
+            (SyntaxKind::Super, expr)
+        } else if self.is_match(&[TokenType::This]) {
+            let expr = Expr::This(ThisExpr {
+                keyword: self.previous().unwrap().clone(),
+                id: Self::next_id(),
+                span: self.previous_span(),
+            });
+            (SyntaxKind::This, expr)
+        } else if self.is_match(&[TokenType::Identifier]) {
+            let expr = Expr::Variable(VariableExpr {
+                name: self.previous().unwrap().clone(),
+                id: Self::next_id(),
+                span: self.previous_span(),
+            });
+            (SyntaxKind::Variable, expr)
+        } else if self.is_match(&[TokenType::LeftParen]) {
+            let left = self.previous_span();
             let expr = self.expression()?;
-            self.consume(
+            let right = self.consume(
                 TokenType::RightParen,
                 "Expect ')' after expression".to_string(),
             )?;
-            return Ok(Expr::Grouping(GroupingExpr {
+            let expr = Expr::Grouping(GroupingExpr {
+                span: left.to(right.get_span()),
                 expression: Box::new(expr),
-            }));
+            });
+            (SyntaxKind::Grouping, expr)
+        } else {
+            return Err(Self::error(
+                ErrorCode::ExpectedExpression,
+                self.peek(),
+                "Expect expression.".to_string(),
+            ));
+        };
+        self.wrap(checkpoint, kind);
+        Ok(expr)
+    }
+
+    /// Parses the rest of an interpolated string after its first Interpolation
+    /// token: an expression after each `${`, and the text between them.
+    fn interpolation(&mut self) -> Result<Expr, LoxError> {
+        let start = self.previous_span();
+        let mut parts = Vec::new();
+        loop {
+            self.push_string_part(&mut parts);
+            parts.push(self.expression()?);
+            if !self.is_match(&[TokenType::InterpolationMiddle]) {
+                break;
+            }
         }
+        self.consume(
+            TokenType::InterpolationEnd,
+            "Expect '}' after interpolated expression.".to_string(),
+        )?;
+        self.push_string_part(&mut parts);
+        Ok(Expr::Interpolation(InterpolationExpr {
+            parts,
+            span: start.to(self.previous_span()),
+        }))
+    }
 
-        Err(LoxError::new(
-            0,
-            "".to_string(),
-            "Expect expression.".to_string(),
-        ))
+    /// Adds the text of the string token just consumed, unless it's empty.
+    fn push_string_part(&self, parts: &mut Vec<Expr>) {
+        let previous = self.previous().unwrap();
+        if let Some(Literal::String(text)) = previous.get_literal() {
+            if !text.is_empty() {
+                parts.push(Expr::Literal(LiteralExpr {
+                    value: previous.get_literal().clone(),
+                    span: previous.get_span(),
+                }));
+            }
+        }
     }
 
     fn consume(&mut self, ttype: TokenType, message: String) -> Result<Token, LoxError> {
         if self.check(ttype) {
             Ok(self.advance().unwrap().clone())
         } else {
-            Err(Parser::error(self.peek().unwrap(), message))
+            Err(Self::error(ErrorCode::ExpectedToken, self.peek(), message))
         }
     }
 
@@ -175,7 +823,7 @@ impl Parser {
             }
 
             if matches!(
-                self.peek().unwrap().get_token_type(),
+                self.peek().get_token_type(),
                 TokenType::Class
                     | TokenType::Fun
                     | TokenType::Var
@@ -203,40 +851,287 @@ impl Parser {
     }
 
     fn check(&self, ttype: TokenType) -> bool {
-        if self.is_at_end() {
-            false
-        } else {
-            match self.peek() {
-                Some(t) => t.get_token_type() == ttype,
-                None => false,
-            }
-        }
+        !self.is_at_end() && self.peek().get_token_type() == ttype
     }
 
     fn advance(&mut self) -> Option<&Token> {
         if !self.is_at_end() {
-            self.current += 1;
+            if let Some(syntax) = &mut self.syntax {
+                syntax.token(&self.current);
+            }
+            let next = self.next_token();
+            self.previous = Some(std::mem::replace(&mut self.current, next));
         }
         self.previous()
     }
 
-    fn is_at_end(&self) -> bool {
-        match self.peek() {
-            Some(t) => t.get_token_type() == TokenType::Eof,
-            None => false,
+    /// Pulls the next token from the stream, recording any lexical errors on the
+    /// way. A stream that ends without an Eof token is treated as if it had one.
+    fn next_token(&mut self) -> Token {
+        loop {
+            match self.tokens.next() {
+                Some(Ok(token)) if token.get_token_type().is_trivia() => {
+                    if let Some(syntax) = &mut self.syntax {
+                        syntax.trivia(token);
+                    }
+                }
+                Some(Ok(token)) => return token,
+                Some(Err(e)) => {
+                    self.errors.push(e);
+                    self.panic_mode = true;
+                }
+                None => return Token::new(TokenType::Eof, "", None, self.current.get_line()),
+            }
         }
     }
 
-    fn peek(&self) -> Option<&Token> {
-        self.tokens.get(self.current)
+    fn is_at_end(&self) -> bool {
+        self.peek().get_token_type() == TokenType::Eof
+    }
+
+    fn peek(&self) -> &Token {
+        &self.current
     }
 
     fn previous(&self) -> Option<&Token> {
-        self.tokens.get(self.current - 1)
+        self.previous.as_ref()
+    }
+
+    fn previous_span(&self) -> Span {
+        self.previous().unwrap().get_span()
+    }
+
+    /// Marks where a node starting at the current token begins.
+    fn checkpoint(&mut self) -> Checkpoint {
+        let start = self.current.get_span().start;
+        match &mut self.syntax {
+            Some(syntax) => syntax.checkpoint(start),
+            None => Checkpoint::default(),
+        }
+    }
+
+    /// Makes a syntax node of everything consumed since `checkpoint`.
+    fn wrap(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        if let Some(syntax) = &mut self.syntax {
+            syntax.wrap(checkpoint, kind);
+        }
+    }
+
+    fn next_id() -> usize {
+        NEXT_EXPR_ID.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn error(code: ErrorCode, token: &Token, message: String) -> LoxError {
+        LoxError::new_with_token(code, token.clone(), message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn parse(source: &str) -> Result<Vec<Stmt>, Vec<LoxError>> {
+        match Parser::new(Scanner::new(source)).parse() {
+            (statements, errors) if errors.is_empty() => Ok(statements),
+            (_, errors) => Err(errors),
+        }
+    }
+
+    #[test]
+    fn test_parse_statements() {
+        let statements = parse("print \"hello\"; 1 + 2;").unwrap();
+        assert_eq!(statements.len(), 2);
+        assert!(matches!(statements[0], Stmt::Print(_)));
+        assert!(matches!(statements[1], Stmt::Expression(_)));
+    }
+
+    #[test]
+    fn test_expression_spans() {
+        let source = "print -(a + b.c) * f(1, 2);";
+        let statements = parse(source).unwrap();
+        let Stmt::Print(print) = &statements[0] else {
+            panic!("expected a print statement");
+        };
+        let span = print.expression.span();
+        assert_eq!(&source[span.start..span.end], "-(a + b.c) * f(1, 2)");
+
+        let Expr::Binary(binary) = &*print.expression else {
+            panic!("expected a binary expression");
+        };
+        let span = binary.right.span();
+        assert_eq!(&source[span.start..span.end], "f(1, 2)");
+    }
+
+    #[test]
+    fn test_missing_semicolon() {
+        assert!(parse("print 1").is_err());
+    }
+
+    #[test]
+    fn test_parse_var_and_assignment() {
+        let statements = parse("var a = 1; var b; a = b = 2;").unwrap();
+        assert_eq!(statements.len(), 3);
+        assert!(matches!(&statements[1], Stmt::Var(v) if v.initializer.is_none()));
+        match &statements[2] {
+            Stmt::Expression(stmt) => {
+                assert!(matches!(&*stmt.expression, Expr::Assign(a) if a.name.get_lexeme() == "a"))
+            }
+            _ => panic!("expected expression statement"),
+        }
+    }
+
+    #[test]
+    fn test_parse_block() {
+        let statements = parse("{ var a = 1; { print a; } }").unwrap();
+        assert_eq!(statements.len(), 1);
+        match &statements[0] {
+            Stmt::Block(block) => {
+                assert_eq!(block.statements.len(), 2);
+                assert!(matches!(block.statements[1], Stmt::Block(_)));
+            }
+            _ => panic!("expected block statement"),
+        }
+        assert!(parse("{ print 1;").is_err());
+    }
+
+    #[test]
+    fn test_desugar_for_loop() {
+        let statements = parse("for (var i = 0; i < 3; i = i + 1) print i;").unwrap();
+        match &statements[0] {
+            Stmt::Block(block) => {
+                assert!(matches!(block.statements[0], Stmt::Var(_)));
+                match &block.statements[1] {
+                    Stmt::While(stmt) => assert!(matches!(*stmt.body, Stmt::Block(_))),
+                    _ => panic!("expected while statement"),
+                }
+            }
+            _ => panic!("expected block statement"),
+        }
+        assert!(matches!(parse("for (;;) print 1;").unwrap()[0], Stmt::While(_)));
+    }
+
+    #[test]
+    fn test_parse_function() {
+        let statements = parse("fun add(a, b) { return a + b; } add(1, 2)(3);").unwrap();
+        match &statements[0] {
+            Stmt::Function(function) => {
+                assert_eq!(function.name.get_lexeme(), "add");
+                assert_eq!(function.params.len(), 2);
+                assert!(matches!(function.body[0], Stmt::Return(_)));
+            }
+            _ => panic!("expected function declaration"),
+        }
+        match &statements[1] {
+            Stmt::Expression(stmt) => match &*stmt.expression {
+                Expr::Call(call) => {
+                    assert_eq!(call.arguments.len(), 1);
+                    assert!(matches!(&*call.callee, Expr::Call(inner) if inner.arguments.len() == 2));
+                }
+                _ => panic!("expected call expression"),
+            },
+            _ => panic!("expected expression statement"),
+        }
+    }
+
+    #[test]
+    fn test_parse_class() {
+        let statements = parse("class B < A { init(x) { this.x = x; } get() { return super.get(); } }").unwrap();
+        match &statements[0] {
+            Stmt::Class(class) => {
+                assert_eq!(class.name.get_lexeme(), "B");
+                assert!(matches!(class.superclass.as_deref(), Some(Expr::Variable(v)) if v.name.get_lexeme() == "A"));
+                assert_eq!(class.methods.len(), 2);
+                match &class.methods[0].body[0] {
+                    Stmt::Expression(stmt) => assert!(matches!(&*stmt.expression, Expr::Set(_))),
+                    _ => panic!("expected expression statement"),
+                }
+            }
+            _ => panic!("expected class declaration"),
+        }
+        assert!(parse("super;").is_err());
+    }
+
+    #[test]
+    fn test_invalid_assignment_target() {
+        assert!(parse("1 = 2;").is_err());
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        let source = "var = 1;\nprint 1;\nfun f( { }\nprint (2;\nvar ok = 3;\n1 = 2;";
+        let (statements, errors) = Parser::new(Scanner::new(source)).parse();
+
+        let messages: Vec<(usize, &str)> = errors
+            .iter()
+            .map(|e| (e.line, e.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (1, "Expect variable name."),
+                (3, "Expect parameter name."),
+                (4, "Expect ')' after expression"),
+                (6, "Invalid assignment target."),
+            ]
+        );
+        // The declarations between the errors still make it into the tree.
+        assert_eq!(statements.len(), 3);
+        assert!(matches!(statements[0], Stmt::Print(_)));
+        assert!(matches!(&statements[1], Stmt::Var(v) if v.name.get_lexeme() == "ok"));
+    }
+
+    #[test]
+    fn test_interpolation_pieces_are_not_operands() {
+        // The `}"` ending an interpolation isn't a string literal, and the
+        // `} b ${` between two expressions doesn't start a new string.
+        for source in [
+            "var x = 1; print \"${x +}\" \"y\";",
+            "print \"${1 +}\";",
+            "print \"${1 +} b ${2}\";",
+        ] {
+            let (_, errors) = Parser::new(Scanner::new(source)).parse();
+            assert_eq!(errors.len(), 1, "{source}");
+            assert_eq!(errors[0].code, ErrorCode::ExpectedExpression, "{source}");
+            let span = errors[0].span.unwrap();
+            assert!(source[span.start..].starts_with('}'), "{source}");
+        }
+        let statements = parse("print \"a ${1} b ${2} c\";").unwrap();
+        let Stmt::Print(print) = &statements[0] else {
+            panic!("expected a print statement");
+        };
+        assert!(matches!(&*print.expression, Expr::Interpolation(i) if i.parts.len() == 5));
+    }
+
+    #[test]
+    fn test_reports_lexical_errors_in_order() {
+        let source = "print 1 @ 2;\nprint \"ok\";\nvar = 1;";
+        let (statements, errors) = Parser::new(Scanner::new(source)).parse();
+        assert_eq!(statements.len(), 1);
+        // The missing ';' after `1` is a knock-on effect of the '@' and isn't reported.
+        let codes: Vec<ErrorCode> = errors.iter().map(|e| e.code).collect();
+        assert_eq!(codes, [ErrorCode::UnexpectedCharacter, ErrorCode::ExpectedToken]);
+        assert_eq!(errors[1].line, 3);
     }
 
-    fn error(token: &Token, message: String) -> LoxError {
-        LoxError::error(token.clone(), message.clone());
-        LoxError::new_with_token(token.clone(), message)
+    #[test]
+    fn test_doc_comments() {
+        let source = "/// A point.\n/// Immutable.\nclass Point {\n  /// Builds one.\n  init() {}\n  other() {}\n}\n\
+            // Not a doc comment.\n//// Nor this.\nfun f() {}\n/// The answer.\nvar x = /// ignored\n 42;";
+        let statements = parse(source).unwrap();
+        let Stmt::Class(class) = &statements[0] else {
+            panic!("expected a class");
+        };
+        assert_eq!(class.doc.as_deref(), Some("A point.\nImmutable."));
+        assert_eq!(class.methods[0].doc.as_deref(), Some("Builds one."));
+        assert_eq!(class.methods[1].doc, None);
+        let Stmt::Function(function) = &statements[1] else {
+            panic!("expected a function");
+        };
+        assert_eq!(function.doc, None);
+        let Stmt::Var(var) = &statements[2] else {
+            panic!("expected a variable");
+        };
+        assert_eq!(var.doc.as_deref(), Some("The answer."));
     }
 }
@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+
+use crate::{
+    error::LoxError,
+    error_code::ErrorCode,
+    expr::*,
+    interpreter::Interpreter,
+    stmt::*,
+    token::Token,
+};
+
+#[derive(Copy, Clone, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Static pass run between parsing and interpreting. It tells the interpreter how
+/// many scopes away each local variable lives and reports scope errors up front.
+pub struct Resolver<'a> {
+    interpreter: &'a mut Interpreter,
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: Vec<LoxError>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Self {
+            interpreter,
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Resolves a whole program, collecting every error found along the way.
+    pub fn resolve(&mut self, statements: &[Stmt]) -> Result<(), Vec<LoxError>> {
+        if let Err(e) = self.resolve_stmts(statements) {
+            self.errors.push(e);
+        }
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn resolve_stmts(&mut self, statements: &[Stmt]) -> Result<(), LoxError> {
+        for statement in statements {
+            statement.accept_mut(self)?;
+        }
+        Ok(())
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) -> Result<(), LoxError> {
+        expr.accept_mut(self)
+    }
+
+    fn resolve_function(
+        &mut self,
+        function: &FunctionStmt,
+        function_type: FunctionType,
+    ) -> Result<(), LoxError> {
+        let enclosing_function = std::mem::replace(&mut self.current_function, function_type);
+
+        self.begin_scope();
+        for param in function.params.iter() {
+            self.declare(param);
+            self.define(param);
+        }
+        let result = self.resolve_stmts(&function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+        result
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+
+        let already_declared = scope.insert(name.get_lexeme().to_string(), false).is_some();
+        if already_declared {
+            self.error(ErrorCode::DuplicateDeclaration, name, "Already a variable with this name in this scope.");
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.get_lexeme().to_string(), true);
+        }
+    }
+
+    fn resolve_local(&mut self, id: usize, name: &Token) {
+        // Anything not found in a local scope is assumed to be global.
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(name.get_lexeme()) {
+                self.interpreter.resolve(id, depth);
+                return;
+            }
+        }
+    }
+
+    fn error(&mut self, code: ErrorCode, token: &Token, message: &str) {
+        self.errors.push(LoxError::new_with_token(code, token.clone(), message.to_string()));
+    }
+}
+
+impl StmtVisitorMut<()> for Resolver<'_> {
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) -> Result<(), LoxError> {
+        self.begin_scope();
+        let result = self.resolve_stmts(&stmt.statements);
+        self.end_scope();
+        result
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) -> Result<(), LoxError> {
+        let enclosing_class = std::mem::replace(&mut self.current_class, ClassType::Class);
+
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+
+        if let Some(superclass) = &stmt.superclass {
+            if let Expr::Variable(variable) = &**superclass {
+                if variable.name.get_lexeme() == stmt.name.get_lexeme() {
+                    self.error(ErrorCode::InheritFromSelf, &variable.name, "A class can't inherit from itself.");
+                }
+            }
+
+            self.current_class = ClassType::Subclass;
+            self.resolve_expr(superclass)?;
+
+            self.begin_scope();
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.insert("super".to_string(), true);
+            }
+        }
+
+        self.begin_scope();
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert("this".to_string(), true);
+        }
+
+        for method in &stmt.methods {
+            let declaration = if method.name.get_lexeme() == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, declaration)?;
+        }
+
+        self.end_scope();
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+        Ok(())
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &ExpressionStmt) -> Result<(), LoxError> {
+        self.resolve_expr(&stmt.expression)
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &FunctionStmt) -> Result<(), LoxError> {
+        // Define eagerly so the function can refer to itself recursively.
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+
+        self.resolve_function(stmt, FunctionType::Function)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) -> Result<(), LoxError> {
+        self.resolve_expr(&stmt.condition)?;
+        stmt.then_branch.accept_mut(self)?;
+        if let Some(else_branch) = &stmt.else_branch {
+            else_branch.accept_mut(self)?;
+        }
+        Ok(())
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) -> Result<(), LoxError> {
+        self.resolve_expr(&stmt.expression)
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) -> Result<(), LoxError> {
+        if self.current_function == FunctionType::None {
+            self.error(ErrorCode::TopLevelReturn, &stmt.keyword, "Can't return from top-level code.");
+        }
+
+        if let Some(value) = &stmt.value {
+            if self.current_function == FunctionType::Initializer {
+                self.errors.push(
+                    LoxError::new_with_token(
+                        ErrorCode::ReturnFromInitializer,
+                        stmt.keyword.clone(),
+                        "Can't return a value from an initializer.".to_string(),
+                    )
+                    .with_note("an initializer always returns 'this'"),
+                );
+            }
+            self.resolve_expr(value)?;
+        }
+        Ok(())
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) -> Result<(), LoxError> {
+        self.declare(&stmt.name);
+        if let Some(initializer) = &stmt.initializer {
+            self.resolve_expr(initializer)?;
+        }
+        self.define(&stmt.name);
+        Ok(())
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) -> Result<(), LoxError> {
+        self.resolve_expr(&stmt.condition)?;
+        stmt.body.accept_mut(self)
+    }
+}
+
+impl ExprVisitorMut<()> for Resolver<'_> {
+    fn visit_assign_expr(&mut self, expr: &AssignExpr) -> Result<(), LoxError> {
+        self.resolve_expr(&expr.value)?;
+        self.resolve_local(expr.id, &expr.name);
+        Ok(())
+    }
+
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr) -> Result<(), LoxError> {
+        self.resolve_expr(&expr.left)?;
+        self.resolve_expr(&expr.right)
+    }
+
+    fn visit_call_expr(&mut self, expr: &CallExpr) -> Result<(), LoxError> {
+        self.resolve_expr(&expr.callee)?;
+        for argument in &expr.arguments {
+            self.resolve_expr(argument)?;
+        }
+        Ok(())
+    }
+
+    fn visit_get_expr(&mut self, expr: &GetExpr) -> Result<(), LoxError> {
+        self.resolve_expr(&expr.object)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &GroupingExpr) -> Result<(), LoxError> {
+        self.resolve_expr(&expr.expression)
+    }
+
+    fn visit_interpolation_expr(&mut self, expr: &InterpolationExpr) -> Result<(), LoxError> {
+        for part in &expr.parts {
+            self.resolve_expr(part)?;
+        }
+        Ok(())
+    }
+
+    fn visit_literal_expr(&mut self, _expr: &LiteralExpr) -> Result<(), LoxError> {
+        Ok(())
+    }
+
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr) -> Result<(), LoxError> {
+        self.resolve_expr(&expr.left)?;
+        self.resolve_expr(&expr.right)
+    }
+
+    fn visit_set_expr(&mut self, expr: &SetExpr) -> Result<(), LoxError> {
+        self.resolve_expr(&expr.value)?;
+        self.resolve_expr(&expr.object)
+    }
+
+    fn visit_super_expr(&mut self, expr: &SuperExpr) -> Result<(), LoxError> {
+        match self.current_class {
+            ClassType::None => self.error(
+                ErrorCode::InvalidSuper,
+                &expr.keyword,
+                "Can't use 'super' outside of a class.",
+            ),
+            ClassType::Class => self.error(
+                ErrorCode::SuperWithoutSuperclass,
+                &expr.keyword,
+                "Can't use 'super' in a class with no superclass.",
+            ),
+            ClassType::Subclass => {}
+        }
+
+        self.resolve_local(expr.id, &expr.keyword);
+        Ok(())
+    }
+
+    fn visit_this_expr(&mut self, expr: &ThisExpr) -> Result<(), LoxError> {
+        if self.current_class == ClassType::None {
+            self.error(ErrorCode::InvalidThis, &expr.keyword, "Can't use 'this' outside of a class.");
+            return Ok(());
+        }
+
+        self.resolve_local(expr.id, &expr.keyword);
+        Ok(())
+    }
+
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr) -> Result<(), LoxError> {
+        self.resolve_expr(&expr.right)
+    }
+
+    fn visit_variable_expr(&mut self, expr: &VariableExpr) -> Result<(), LoxError> {
+        let declared_but_undefined = self
+            .scopes
+            .last()
+            .and_then(|scope| scope.get(expr.name.get_lexeme()).copied())
+            == Some(false);
+        if declared_but_undefined {
+            self.error(
+                ErrorCode::ReadInOwnInitializer,
+                &expr.name,
+                "Can't read local variable in its own initializer.",
+            );
+        }
+
+        self.resolve_local(expr.id, &expr.name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn resolve(source: &str) -> Result<(), Vec<LoxError>> {
+        let (statements, _) = Parser::new(Scanner::new(source)).parse();
+        let mut interpreter = Interpreter::new();
+        Resolver::new(&mut interpreter).resolve(&statements)
+    }
+
+    fn resolve_error(source: &str) -> String {
+        resolve(source).err().unwrap()[0].message.clone()
+    }
+
+    #[test]
+    fn test_valid_program() {
+        assert!(resolve("var a = 1; { var b = a; fun f() { return b; } }").is_ok());
+        assert!(resolve("class A { m() { return this; } } class B < A { m() { return super.m(); } }").is_ok());
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        let errors = resolve("return 1; print this; { var a; var a; }").err().unwrap();
+        let codes: Vec<ErrorCode> = errors.iter().map(|e| e.code).collect();
+        assert_eq!(
+            codes,
+            [
+                ErrorCode::TopLevelReturn,
+                ErrorCode::InvalidThis,
+                ErrorCode::DuplicateDeclaration
+            ]
+        );
+    }
+
+    #[test]
+    fn test_static_errors() {
+        assert_eq!(
+            resolve_error("{ var a = 1; var a = 2; }"),
+            "Already a variable with this name in this scope."
+        );
+        assert_eq!(
+            resolve_error("{ var a = a; }"),
+            "Can't read local variable in its own initializer."
+        );
+        assert_eq!(resolve_error("return 1;"), "Can't return from top-level code.");
+        assert_eq!(
+            resolve_error("class A { init() { return 1; } }"),
+            "Can't return a value from an initializer."
+        );
+        assert_eq!(resolve_error("print this;"), "Can't use 'this' outside of a class.");
+        assert_eq!(resolve_error("class A < A {}"), "A class can't inherit from itself.");
+        assert_eq!(
+            resolve_error("fun f() { super.g(); }"),
+            "Can't use 'super' outside of a class."
+        );
+        assert_eq!(
+            resolve_error("class A { m() { super.m(); } }"),
+            "Can't use 'super' in a class with no superclass."
+        );
+    }
+}
@@ -1,89 +1,126 @@
-use std::collections::HashMap;
+use std::{ops::Range, rc::Rc};
 
 use crate::{
-    error::{self, LoxError},
-    token::{Literal, Token, TokenType},
-    util,
+    error::LoxError,
+    error_code::ErrorCode,
+    token::{Literal, Span, Token, TokenType},
+    unicode,
 };
 
+/// Turns source text into tokens in a single pass. Positions are byte offsets
+/// into `source`, so looking at the next character and slicing out a lexeme
+/// are both constant time.
 pub struct Scanner {
-    source: String,
-    tokens: Vec<Token>,
+    // Shared with every token, which slices its lexeme out of it.
+    source: Rc<str>,
     current: usize,
     start: usize,
     line: usize,
-    keywords: HashMap<String, TokenType>,
+    // Chars between the start of the line and `current`, and the same for `start`.
+    column: usize,
+    start_column: usize,
+    // Strings whose `${` is open, innermost last.
+    interpolations: Vec<Interpolation>,
+    // `///` comments seen since the last token, waiting to be attached to the next.
+    doc: Option<String>,
+    // Whether whitespace and comments are returned as tokens.
+    trivia: bool,
+    // Set once the Eof token has been handed out.
+    done: bool,
+}
+
+/// Where a string literal's opening quote is, for reporting it unterminated.
+#[derive(Copy, Clone)]
+struct Quote {
+    start: usize,
+    line: usize,
+    column: usize,
+}
+
+/// A `${` inside a string that hasn't been closed yet.
+struct Interpolation {
+    quote: Quote,
+    // Unmatched '{' seen since the `${`, so that the right '}' resumes the string.
+    braces: usize,
 }
 
 impl Scanner {
-    pub fn new(source: String) -> Self {
-        let keywords = {
-            let mut map = HashMap::new();
-            map.insert("and".to_string(), TokenType::And);
-            map.insert("class".to_string(), TokenType::Class);
-            map.insert("else".to_string(), TokenType::Else);
-            map.insert("false".to_string(), TokenType::False);
-            map.insert("for".to_string(), TokenType::For);
-            map.insert("fun".to_string(), TokenType::Fun);
-            map.insert("if".to_string(), TokenType::If);
-            map.insert("nil".to_string(), TokenType::Nil);
-            map.insert("or".to_string(), TokenType::Or);
-            map.insert("print".to_string(), TokenType::Print);
-            map.insert("return".to_string(), TokenType::Return);
-            map.insert("super".to_string(), TokenType::Super);
-            map.insert("this".to_string(), TokenType::This);
-            map.insert("true".to_string(), TokenType::True);
-            map.insert("var".to_string(), TokenType::Var);
-            map.insert("while".to_string(), TokenType::While);
-            map
-        };
+    pub fn new(source: &str) -> Self {
         Self {
-            source,
-            tokens: Vec::new(),
+            source: source.into(),
             current: 0,
             start: 0,
             line: 1,
-            keywords,
+            column: 0,
+            start_column: 0,
+            interpolations: Vec::new(),
+            doc: None,
+            trivia: false,
+            done: false,
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, LoxError> {
-        let mut had_error = false;
-        while !self.is_end() {
-            self.start = self.current;
-            if let Err(_e) = self.scan_token() {
-                had_error = true;
-            }
+    /// A scanner for the part of `source` from byte `start` on, which must begin
+    /// a line. Spans and line numbers still count from the start of `source`.
+    pub fn starting_at(source: &str, start: usize) -> Self {
+        Self {
+            current: start,
+            line: source[..start].matches('\n').count() + 1,
+            ..Self::new(source)
         }
-        self.tokens
-            .push(Token::new(TokenType::Eof, "".to_string(), None, self.line));
-        if had_error {
-            return Err(LoxError::new(0, "".to_string(), "".to_string()));
+    }
+
+    /// A scanner that also returns whitespace and comments, as Whitespace and
+    /// Comment tokens, so that every byte of the source is in some token.
+    pub fn with_trivia(source: &str) -> Self {
+        Self {
+            trivia: true,
+            ..Self::new(source)
         }
-        Ok(self.tokens.clone())
     }
 
-    fn scan_token(&mut self) -> Result<(), LoxError> {
-        let mut had_error = false;
+    fn begin_token(&mut self) {
+        self.start = self.current;
+        self.start_column = self.column;
+    }
+
+    /// Scans one lexeme, returning `None` for whitespace and comments unless
+    /// trivia is being scanned.
+    fn scan_token(&mut self) -> Result<Option<Token>, LoxError> {
         let c = self.advance();
-        match c {
-            '(' => self.add_token(TokenType::LeftParen),
-            ')' => self.add_token(TokenType::RightParen),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
-            ',' => self.add_token(TokenType::Comma),
-            '.' => self.add_token(TokenType::Dot),
-            '-' => self.add_token(TokenType::Minus),
-            '+' => self.add_token(TokenType::Plus),
-            ';' => self.add_token(TokenType::Semicolon),
-            '*' => self.add_token(TokenType::Star),
+        let token = match c {
+            '(' => self.make_token(TokenType::LeftParen),
+            ')' => self.make_token(TokenType::RightParen),
+            '{' => {
+                if let Some(interpolation) = self.interpolations.last_mut() {
+                    interpolation.braces += 1;
+                }
+                self.make_token(TokenType::LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(interpolation) if interpolation.braces == 0 => {
+                    let quote = self.interpolations.pop().unwrap().quote;
+                    self.string(quote, true)?
+                }
+                Some(interpolation) => {
+                    interpolation.braces -= 1;
+                    self.make_token(TokenType::RightBrace)
+                }
+                None => self.make_token(TokenType::RightBrace),
+            },
+            ',' => self.make_token(TokenType::Comma),
+            '.' => self.make_token(TokenType::Dot),
+            '-' => self.make_token(TokenType::Minus),
+            '+' => self.make_token(TokenType::Plus),
+            ';' => self.make_token(TokenType::Semicolon),
+            '*' => self.make_token(TokenType::Star),
             '!' => {
                 let ty = if self.is_match('=') {
                     TokenType::BangEqual
                 } else {
                     TokenType::Bang
                 };
-                self.add_token(ty)
+                self.make_token(ty)
             }
             '=' => {
                 let ty = if self.is_match('=') {
@@ -91,7 +128,7 @@ impl Scanner {
                 } else {
                     TokenType::Equal
                 };
-                self.add_token(ty)
+                self.make_token(ty)
             }
             '<' => {
                 let ty = if self.is_match('=') {
@@ -99,7 +136,7 @@ impl Scanner {
                 } else {
                     TokenType::Less
                 };
-                self.add_token(ty)
+                self.make_token(ty)
             }
             '>' => {
                 let ty = if self.is_match('=') {
@@ -107,146 +144,390 @@ impl Scanner {
                 } else {
                     TokenType::Greater
                 };
-                self.add_token(ty)
+                self.make_token(ty)
             }
             '/' => {
                 if self.is_match('/') {
-                    // It's a comment
-                    while self.peek() != '\n' && !self.is_end() {
+                    // `///` starts a doc comment, but `////` is an ordinary one.
+                    let is_doc = self.peek() == '/' && self.peek_next() != '/';
+                    let text_start = self.current + 1;
+                    // The '\r' of a "\r\n" line ending is whitespace, not comment.
+                    while self.peek() != '\n'
+                        && !(self.peek() == '\r' && self.peek_next() == '\n')
+                        && !self.is_end()
+                    {
                         self.advance();
                     }
-                } else {
-                    self.add_token(TokenType::Slash)
+                    if is_doc {
+                        self.doc_line(text_start);
+                    }
+                    return Ok(self.trivia_token(TokenType::Comment));
+                }
+                if self.is_match('*') {
+                    self.block_comment()?;
+                    return Ok(self.trivia_token(TokenType::Comment));
                 }
+                self.make_token(TokenType::Slash)
             }
-            ' ' => {}
-            '\r' => {}
-            '\t' => {}
-            '\n' => {
-                self.line += 1;
+            ' ' | '\r' | '\t' | '\n' => {
+                if c == '\n' {
+                    self.newline();
+                }
+                while matches!(self.peek(), ' ' | '\r' | '\t' | '\n') {
+                    if self.advance() == '\n' {
+                        self.newline();
+                    }
+                }
+                return Ok(self.trivia_token(TokenType::Whitespace));
             }
             '"' => {
-                if let Err(_e) = self.string() {
-                    had_error = true;
-                }
+                let quote = Quote {
+                    start: self.start,
+                    line: self.line,
+                    column: self.column(),
+                };
+                self.string(quote, false)?
             }
             _ => {
-                if c.is_numeric() {
-                    self.number()
-                } else if c.is_alphabetic() {
+                if c.is_ascii_digit() {
+                    self.number()?
+                } else if unicode::is_identifier_start(c) {
                     self.identifier()
                 } else {
-                    let e = error::LoxError::new(
-                        self.line,
-                        "".to_string(),
-                        "Unexpected character".to_string(),
-                    );
-                    eprintln!("{}", e.to_string());
-                    return Err(e);
+                    return Err(self.error(ErrorCode::UnexpectedCharacter, "Unexpected character."));
                 }
             }
-        }
-        if had_error {
-            return Err(LoxError::new(0, "".to_string(), "".to_string()));
+        };
+        Ok(Some(token))
+    }
+
+    /// Skips a `/* ... */` comment, including any nested in it, after its `/*`.
+    fn block_comment(&mut self) -> Result<(), LoxError> {
+        let line = self.line;
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_end() {
+                return Err(LoxError::new_with_span(
+                    ErrorCode::UnterminatedComment,
+                    line,
+                    self.column(),
+                    Span::new(self.start, self.start + 2),
+                    "Unterminated block comment.".to_string(),
+                )
+                .with_label("comment starts here")
+                .with_help("add a closing '*/'"));
+            }
+            match self.advance() {
+                '/' if self.peek() == '*' => {
+                    self.advance();
+                    depth += 1;
+                }
+                '*' if self.peek() == '/' => {
+                    self.advance();
+                    depth -= 1;
+                }
+                '\n' => self.newline(),
+                _ => {}
+            }
         }
         Ok(())
     }
 
+    /// Adds the text of the `///` comment from byte `start` up to `current` to
+    /// the pending doc comment.
+    fn doc_line(&mut self, start: usize) {
+        let text = &self.source[start..self.current];
+        let text = text.strip_prefix(' ').unwrap_or(text).trim_end();
+        match &mut self.doc {
+            Some(doc) => {
+                doc.push('\n');
+                doc.push_str(text);
+            }
+            None => self.doc = Some(text.to_string()),
+        }
+    }
+
     fn advance(&mut self) -> char {
-        let c = self.source.chars().nth(self.current).unwrap();
-        self.current += 1;
+        let c = self.peek();
+        self.current += c.len_utf8();
+        self.column += 1;
         c
     }
 
+    /// Called after consuming a '\n' so columns restart on the next line.
+    fn newline(&mut self) {
+        self.line += 1;
+        self.column = 0;
+    }
+
+    fn column(&self) -> usize {
+        self.start_column + 1
+    }
+
+    fn span(&self) -> Span {
+        Span::new(self.start, self.current)
+    }
+
+    fn lexeme(&self) -> &str {
+        &self.source[self.start..self.current]
+    }
+
+    fn error(&self, code: ErrorCode, message: &str) -> LoxError {
+        LoxError::new_with_span(code, self.line, self.column(), self.span(), message.to_string())
+    }
+
     fn peek(&self) -> char {
+        self.char_at(self.current)
+    }
+
+    fn peek_next(&self) -> char {
         if self.is_end() {
             return '\0';
         }
-        return self.source.chars().nth(self.current).unwrap();
+        self.char_at(self.current + self.peek().len_utf8())
     }
 
-    fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
+    /// Decodes the char starting at byte `index`, or '\0' past the end.
+    fn char_at(&self, index: usize) -> char {
+        match self.source.as_bytes().get(index) {
+            None => '\0',
+            Some(&byte) if byte.is_ascii() => byte as char,
+            Some(_) => self.source[index..].chars().next().unwrap(),
         }
-        self.source.chars().nth(self.current + 1).unwrap()
     }
 
-    fn string(&mut self) -> Result<(), LoxError> {
+    /// Scans string contents up to the closing quote, or up to a `${` which
+    /// makes an Interpolation token. Called after the opening quote, or
+    /// `resumed` after the '}' that ends an interpolated expression, whose
+    /// tokens have their own types so that they can't start an expression.
+    fn string(&mut self, quote: Quote, resumed: bool) -> Result<Token, LoxError> {
+        // A string may span lines; the token keeps the line and column it started on.
+        let (line, column) = (self.line, self.column());
+        let mut value = String::new();
+        // An invalid escape is reported once the whole string has been consumed,
+        // so scanning resumes after the closing quote rather than inside the string.
+        let mut escape_error = None;
         while self.peek() != '"' && !self.is_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            if self.peek() == '$' && self.peek_next() == '{' {
+                self.advance();
+                self.advance();
+                self.interpolations.push(Interpolation { quote, braces: 0 });
+                if let Some(e) = escape_error {
+                    return Err(e);
+                }
+                let ty = if resumed {
+                    TokenType::InterpolationMiddle
+                } else {
+                    TokenType::Interpolation
+                };
+                return Ok(self.string_token(ty, value, line, column));
+            }
+            match self.advance() {
+                '\n' => {
+                    self.newline();
+                    value.push('\n');
+                }
+                '\\' => match self.escape() {
+                    Ok(c) => value.push(c),
+                    Err(e) => {
+                        escape_error.get_or_insert(e);
+                    }
+                },
+                c => value.push(c),
             }
-            self.advance();
         }
 
         if self.is_end() {
-            let e = LoxError::new(
-                self.line,
-                "".to_string(),
-                "Unexpected character.".to_string(),
-            );
-            eprintln!("{}", e);
-            return Err(e);
+            return Err(Self::unterminated(quote));
         }
 
         self.advance();
-        let value = util::substring(&self.source, self.start + 1, self.current - 1);
-        self.add_token_literal(TokenType::String, Some(Literal::String(value.to_string())));
-        Ok(())
+        if let Some(e) = escape_error {
+            return Err(e);
+        }
+        let ty = if resumed {
+            TokenType::InterpolationEnd
+        } else {
+            TokenType::String
+        };
+        Ok(self.string_token(ty, value, line, column))
     }
 
-    fn number(&mut self) {
-        while self.peek().is_numeric() {
+    fn string_token(&self, ty: TokenType, value: String, line: usize, column: usize) -> Token {
+        Token::from_source(
+            ty,
+            Rc::clone(&self.source),
+            Some(Literal::String(value)),
+            line,
+            column,
+            self.span(),
+        )
+    }
+
+    fn unterminated(quote: Quote) -> LoxError {
+        LoxError::new_with_span(
+            ErrorCode::UnterminatedString,
+            quote.line,
+            quote.column,
+            Span::new(quote.start, quote.start + 1),
+            "Unterminated string.".to_string(),
+        )
+        .with_label("string starts here")
+        .with_help("add a closing '\"'")
+    }
+
+    /// Decodes the escape sequence after a '\' inside a string.
+    fn escape(&mut self) -> Result<char, LoxError> {
+        let (line, column) = (self.line, self.column);
+        let start = self.current - 1;
+        let error = |scanner: &Self, message: String| {
+            LoxError::new_with_span(
+                ErrorCode::InvalidEscape,
+                line,
+                column,
+                Span::new(start, scanner.current),
+                message,
+            )
+        };
+        if self.is_end() {
+            return Err(error(self, "Unterminated escape sequence.".to_string()));
+        }
+        // A "\r\n" line ending is handled as its '\n'.
+        if self.peek() == '\r' && self.peek_next() == '\n' {
             self.advance();
         }
+        let c = match self.advance() {
+            '"' => '"',
+            '\\' => '\\',
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '$' => '$',
+            'u' => return self.unicode_escape().map_err(|message| error(self, message)),
+            '\n' => {
+                self.newline();
+                return Err(error(self, "A '\\' can't end a line inside a string.".to_string())
+                    .with_help("strings may span lines without an escape"));
+            }
+            c => {
+                return Err(error(self, format!("Unknown escape sequence '\\{c}'."))
+                    .with_label("unknown escape")
+                    .with_help("valid escapes are \\\" \\\\ \\n \\t \\r \\0 \\$ and \\u{...}"));
+            }
+        };
+        Ok(c)
+    }
 
-        if self.peek() == '.' && self.peek_next().is_numeric() {
+    /// Decodes the `{...}` of a `\u{...}` escape: one to six hex digits naming a
+    /// Unicode scalar value.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        if !self.is_match('{') {
+            return Err("Expect '{' after '\\u'.".to_string());
+        }
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
             self.advance();
+        }
+        let digits_end = self.current;
+        if !self.is_match('}') {
+            return Err("Expect hex digits and '}' in '\\u{...}' escape.".to_string());
+        }
+        let digits = &self.source[digits_start..digits_end];
+        if digits.is_empty() || digits.len() > 6 {
+            return Err("A '\\u{...}' escape takes one to six hex digits.".to_string());
+        }
+        let code = u32::from_str_radix(digits, 16).unwrap();
+        char::from_u32(code).ok_or_else(|| format!("'\\u{{{digits}}}' is not a Unicode scalar value."))
+    }
 
-            while self.peek().is_numeric() {
+    /// Scans a number literal after its first digit. Letters directly after
+    /// it are scanned as part of it, so `0x1g` or `12ab` is reported as one
+    /// malformed number rather than a number followed by a name.
+    fn number(&mut self) -> Result<Token, LoxError> {
+        let radix_prefix = self.lexeme() == "0" && matches!(self.peek(), 'x' | 'b' | 'o');
+        if radix_prefix {
+            self.advance();
+            self.digits(false);
+        } else {
+            self.digits(true);
+            if self.peek() == '.' && self.peek_next().is_ascii_digit() {
                 self.advance();
+                self.digits(true);
+            }
+            if matches!(self.peek(), 'e' | 'E') {
+                self.advance();
+                if matches!(self.peek(), '+' | '-') {
+                    self.advance();
+                }
+                self.digits(false);
             }
         }
 
-        self.add_token_literal(
-            TokenType::Number,
-            Some(Literal::Number(
-                util::substring(&self.source, self.start, self.current)
-                    .to_string()
-                    .parse::<f64>()
-                    .unwrap(),
-            )),
-        )
+        match parse_number(self.lexeme()) {
+            Ok(value) => Ok(self.make_token_literal(TokenType::Number, Some(Literal::Number(value)))),
+            Err((range, message)) => {
+                let before = &self.lexeme()[..range.start];
+                Err(LoxError::new_with_span(
+                    ErrorCode::InvalidNumber,
+                    self.line,
+                    self.column() + before.chars().count(),
+                    Span::new(self.start + range.start, self.start + range.end),
+                    message,
+                ))
+            }
+        }
     }
 
-    fn identifier(&mut self) {
-        while self.peek().is_alphabetic() || self.peek() == '_' {
+    /// Consumes digits, '_' separators and any letters mixed in with them,
+    /// stopping before an 'e' or 'E' if an exponent may follow.
+    fn digits(&mut self, exponent_may_follow: bool) {
+        loop {
+            let c = self.peek();
+            if exponent_may_follow && matches!(c, 'e' | 'E') {
+                break;
+            }
+            if !c.is_ascii_digit() && !unicode::is_identifier_continue(c) {
+                break;
+            }
             self.advance();
         }
-        let text = util::substring(&self.source, self.start, self.current);
-        let ty = self.keywords.get(text).unwrap_or(&TokenType::Identifier);
-        self.add_token(ty.to_owned());
     }
 
-    fn add_token(&mut self, ty: TokenType) {
-        self.add_token_literal(ty, None);
+    fn identifier(&mut self) -> Token {
+        while unicode::is_identifier_continue(self.peek()) {
+            self.advance();
+        }
+        self.make_token(keyword(self.lexeme()).unwrap_or(TokenType::Identifier))
     }
 
-    fn add_token_literal(&mut self, ty: TokenType, literal: Option<Literal>) {
-        let text = util::substring(&self.source, self.start, self.current).to_string();
-        // let result = self.source.get(self.start..self.current).unwrap();
-        self.tokens.push(Token::new(ty, text, literal, self.line));
+    fn trivia_token(&self, ty: TokenType) -> Option<Token> {
+        self.trivia.then(|| self.make_token(ty))
+    }
+
+    fn make_token(&self, ty: TokenType) -> Token {
+        self.make_token_literal(ty, None)
+    }
+
+    fn make_token_literal(&self, ty: TokenType, literal: Option<Literal>) -> Token {
+        Token::from_source(
+            ty,
+            Rc::clone(&self.source),
+            literal,
+            self.line,
+            self.column(),
+            self.span(),
+        )
     }
 
     fn is_match(&mut self, expected: char) -> bool {
         if self.is_end() {
             return false;
         }
-        if expected != self.source.chars().nth(self.current).unwrap() {
+        if expected != self.peek() {
             return false;
         }
-        self.current += 1;
+        self.advance();
         true
     }
 
@@ -254,3 +535,483 @@ impl Scanner {
         self.current >= self.source.len()
     }
 }
+
+/// The keyword spelled by `text`, if it is one.
+fn keyword(text: &str) -> Option<TokenType> {
+    let ty = match text {
+        "and" => TokenType::And,
+        "class" => TokenType::Class,
+        "else" => TokenType::Else,
+        "false" => TokenType::False,
+        "for" => TokenType::For,
+        "fun" => TokenType::Fun,
+        "if" => TokenType::If,
+        "nil" => TokenType::Nil,
+        "or" => TokenType::Or,
+        "print" => TokenType::Print,
+        "return" => TokenType::Return,
+        "super" => TokenType::Super,
+        "this" => TokenType::This,
+        "true" => TokenType::True,
+        "var" => TokenType::Var,
+        "while" => TokenType::While,
+        _ => return None,
+    };
+    Some(ty)
+}
+
+/// Evaluates the text of a number literal: decimal with an optional fraction
+/// and exponent, or an integer after `0x`, `0b` or `0o`, with '_' allowed
+/// between digits. On failure, returns the byte range within `text` at fault
+/// and a message.
+fn parse_number(text: &str) -> Result<f64, (Range<usize>, String)> {
+    let (radix, name) = match text.get(..2) {
+        Some("0x") => (16, "hexadecimal"),
+        Some("0b") => (2, "binary"),
+        Some("0o") => (8, "octal"),
+        _ => (10, "decimal"),
+    };
+    if radix != 10 {
+        let digits = check_digits(text, 2..text.len(), radix, name)?;
+        if digits.is_empty() {
+            return Err((0..2, format!("Expect {name} digits after '{}'.", &text[..2])));
+        }
+        let value = digits
+            .chars()
+            .filter_map(|c| c.to_digit(radix))
+            .fold(0.0, |value, digit| value * radix as f64 + digit as f64);
+        return finite(text, value);
+    }
+
+    let exponent = text.find(['e', 'E']);
+    let mantissa_end = exponent.unwrap_or(text.len());
+    match text[..mantissa_end].find('.') {
+        Some(dot) => {
+            check_digits(text, 0..dot, 10, name)?;
+            check_digits(text, dot + 1..mantissa_end, 10, name)?;
+        }
+        None => {
+            check_digits(text, 0..mantissa_end, 10, name)?;
+        }
+    }
+    if let Some(e) = exponent {
+        let sign = usize::from(matches!(text[e + 1..].chars().next(), Some('+' | '-')));
+        let digits = check_digits(text, e + 1 + sign..text.len(), 10, name)?;
+        if digits.is_empty() {
+            return Err((e..text.len(), "Expect digits in exponent.".to_string()));
+        }
+    }
+
+    finite(text, text.replace('_', "").parse().unwrap())
+}
+
+/// `value`, unless it overflowed to infinity, which is reported over all of
+/// the number's `text`.
+fn finite(text: &str, value: f64) -> Result<f64, (Range<usize>, String)> {
+    if value.is_infinite() {
+        return Err((0..text.len(), "Number is too large.".to_string()));
+    }
+    Ok(value)
+}
+
+/// Checks that `text[range]` holds only digits in `radix` and '_' separators,
+/// each separator between two digits, and returns that slice.
+fn check_digits<'t>(
+    text: &'t str,
+    range: Range<usize>,
+    radix: u32,
+    name: &str,
+) -> Result<&'t str, (Range<usize>, String)> {
+    let digits = &text[range.clone()];
+    for (i, c) in digits.char_indices() {
+        let at = range.start + i..range.start + i + c.len_utf8();
+        if c == '_' {
+            let is_digit = |c: char| c.is_digit(radix);
+            if !digits[..i].ends_with(is_digit) || !digits[i + 1..].starts_with(is_digit) {
+                return Err((at, "A '_' in a number must be between two digits.".to_string()));
+            }
+        } else if !c.is_digit(radix) {
+            return Err((at, format!("Invalid digit '{c}' in {name} number.")));
+        }
+    }
+    Ok(digits)
+}
+
+/// Tokens are scanned on demand, so a parser can consume them as they are
+/// produced. A lexical error is yielded in place of the token and scanning
+/// carries on after it; the last item is always the Eof token.
+impl Iterator for Scanner {
+    type Item = Result<Token, LoxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        while !self.is_end() {
+            self.begin_token();
+            match self.scan_token() {
+                Ok(Some(token)) if token.get_token_type().is_trivia() => return Some(Ok(token)),
+                Ok(Some(token)) => return Some(Ok(token.with_doc(self.doc.take()))),
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        // An interpolated expression left open at the end also leaves its string open.
+        if let Some(interpolation) = self.interpolations.pop() {
+            return Some(Err(Self::unterminated(interpolation.quote)));
+        }
+        self.done = true;
+        self.begin_token();
+        Some(Ok(self.make_token(TokenType::Eof)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn positions(source: &str) -> Vec<(String, usize, usize, Span)> {
+        Scanner::new(source)
+            .map(|t| t.unwrap())
+            .map(|t| (t.get_lexeme().to_string(), t.get_line(), t.get_column(), t.get_span()))
+            .collect()
+    }
+
+    #[test]
+    fn test_token_positions() {
+        let tokens = positions("var abc = 12.5;\n  print \"x\ny\" >= abc;");
+        let expected = [
+            ("var", 1, 1, Span::new(0, 3)),
+            ("abc", 1, 5, Span::new(4, 7)),
+            ("=", 1, 9, Span::new(8, 9)),
+            ("12.5", 1, 11, Span::new(10, 14)),
+            (";", 1, 15, Span::new(14, 15)),
+            ("print", 2, 3, Span::new(18, 23)),
+            ("\"x\ny\"", 2, 9, Span::new(24, 29)),
+            (">=", 3, 4, Span::new(30, 32)),
+            ("abc", 3, 7, Span::new(33, 36)),
+            (";", 3, 10, Span::new(36, 37)),
+            ("", 3, 11, Span::new(37, 37)),
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for (token, (lexeme, line, column, span)) in tokens.iter().zip(expected) {
+            assert_eq!(token, &(lexeme.to_string(), line, column, span));
+        }
+    }
+
+    #[test]
+    fn test_starting_at_keeps_positions() {
+        let source = "var a;\nprint a;";
+        let tokens: Vec<(String, usize, usize, Span)> = Scanner::starting_at(source, 7)
+            .map(|t| t.unwrap())
+            .map(|t| (t.get_lexeme().to_string(), t.get_line(), t.get_column(), t.get_span()))
+            .collect();
+        assert_eq!(tokens[0], ("print".to_string(), 2, 1, Span::new(7, 12)));
+        assert_eq!(tokens.len(), 4);
+    }
+
+    #[test]
+    fn test_unicode_positions() {
+        // "cafe\u{301}" spells café with a combining accent.
+        let source = "print \"👋 世界\" + 名前; // ✓\nvar cafe\u{301} = 1;";
+        let tokens = positions(source);
+        let expected = [
+            ("print", 1, 1, Span::new(0, 5)),
+            ("\"👋 世界\"", 1, 7, Span::new(6, 19)),
+            ("+", 1, 14, Span::new(20, 21)),
+            ("名前", 1, 16, Span::new(22, 28)),
+            (";", 1, 18, Span::new(28, 29)),
+            ("var", 2, 1, Span::new(37, 40)),
+            ("cafe\u{301}", 2, 5, Span::new(41, 47)),
+            ("=", 2, 11, Span::new(48, 49)),
+            ("1", 2, 13, Span::new(50, 51)),
+            (";", 2, 14, Span::new(51, 52)),
+            ("", 2, 15, Span::new(52, 52)),
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for (token, (lexeme, line, column, span)) in tokens.iter().zip(expected) {
+            assert_eq!(token, &(lexeme.to_string(), line, column, span));
+        }
+
+        let string = Scanner::new(source).nth(1).unwrap().unwrap();
+        assert_eq!(string.get_literal(), &Some(Literal::String("👋 世界".to_string())));
+    }
+
+    #[test]
+    fn test_unexpected_unicode_character() {
+        let errors: Vec<LoxError> = Scanner::new("var x = \"é\" 👋;").filter_map(Result::err).collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, ErrorCode::UnexpectedCharacter);
+        assert_eq!((errors[0].line, errors[0].column), (1, 13));
+        assert_eq!(errors[0].span, Some(Span::new(13, 17)));
+    }
+
+    fn string_value(source: &str) -> String {
+        match Scanner::new(source).next().unwrap().unwrap().get_literal() {
+            Some(Literal::String(value)) => value.clone(),
+            other => panic!("expected a string literal, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        assert_eq!(
+            string_value(r#""a\"b\\c\nd\te\rf\0g\u{48}\u{1F600}""#),
+            "a\"b\\c\nd\te\rf\0gH😀"
+        );
+        assert_eq!(string_value("\"two\nlines\""), "two\nlines");
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        let errors: Vec<LoxError> = Scanner::new(r#"print "a \q b \u{D800}"; print "\u{}";"#)
+            .filter_map(Result::err)
+            .collect();
+        // Only the first bad escape in a string is reported.
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| e.code == ErrorCode::InvalidEscape));
+        assert_eq!(errors[0].message, "Unknown escape sequence '\\q'.");
+        assert_eq!((errors[0].column, errors[0].span), (10, Some(Span::new(9, 11))));
+        assert_eq!(errors[1].span, Some(Span::new(32, 36)));
+
+        for (source, message) in [
+            (r#""\u{110000}""#, "'\\u{110000}' is not a Unicode scalar value."),
+            (r#""\u{1234567}""#, "A '\\u{...}' escape takes one to six hex digits."),
+            (r#""\u41""#, "Expect '{' after '\\u'."),
+            (r#""\u{4g}""#, "Expect hex digits and '}' in '\\u{...}' escape."),
+        ] {
+            let error = Scanner::new(source).next().unwrap().unwrap_err();
+            assert_eq!(error.message, message);
+        }
+
+        // Scanning picks up again after the string's closing quote.
+        let tokens: Vec<TokenType> = Scanner::new(r#""\x" + 1"#)
+            .filter_map(Result::ok)
+            .map(|t| t.get_token_type())
+            .collect();
+        assert_eq!(tokens, [TokenType::Plus, TokenType::Number, TokenType::Eof]);
+    }
+
+    #[test]
+    fn test_backslash_ending_a_line() {
+        for (source, span) in [("\"abc\\\n def\";", (4, 6)), ("\"abc\\\r\n def\";", (4, 7))] {
+            let errors: Vec<LoxError> = Scanner::new(source).filter_map(Result::err).collect();
+            assert_eq!(errors.len(), 1, "{source:?}");
+            assert_eq!(errors[0].message, "A '\\' can't end a line inside a string.", "{source:?}");
+            assert_eq!(errors[0].span, Some(Span::new(span.0, span.1)), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn test_unterminated_string_points_at_opening_quote() {
+        let errors: Vec<LoxError> = Scanner::new("var s = \"abc\\\"\n  def;\n")
+            .filter_map(Result::err)
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, ErrorCode::UnterminatedString);
+        assert_eq!((errors[0].line, errors[0].column), (1, 9));
+        assert_eq!(errors[0].span, Some(Span::new(8, 9)));
+    }
+
+    #[test]
+    fn test_interpolation_tokens() {
+        let source = r#""a ${b + "c${d}"} e""#;
+        let tokens: Vec<(TokenType, String, Option<Literal>)> = Scanner::new(source)
+            .map(|t| t.unwrap())
+            .map(|t| (t.get_token_type(), t.get_lexeme().to_string(), t.get_literal().clone()))
+            .collect();
+        let text = |s: &str| Some(Literal::String(s.to_string()));
+        assert_eq!(
+            tokens,
+            [
+                (TokenType::Interpolation, "\"a ${".to_string(), text("a ")),
+                (TokenType::Identifier, "b".to_string(), None),
+                (TokenType::Plus, "+".to_string(), None),
+                (TokenType::Interpolation, "\"c${".to_string(), text("c")),
+                (TokenType::Identifier, "d".to_string(), None),
+                (TokenType::InterpolationEnd, "}\"".to_string(), text("")),
+                (TokenType::InterpolationEnd, "} e\"".to_string(), text(" e")),
+                (TokenType::Eof, "".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_interpolation() {
+        // Whether the string or the expression is left open, the error points
+        // at the quote that opened the string.
+        for source in ["x = \"a ${b} c", "x = \"a ${b + 1"] {
+            let errors: Vec<LoxError> = Scanner::new(source).filter_map(Result::err).collect();
+            assert_eq!(errors.len(), 1, "{source}");
+            assert_eq!(errors[0].code, ErrorCode::UnterminatedString);
+            assert_eq!(errors[0].span, Some(Span::new(4, 5)));
+        }
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let source = "a /* one\n /* two */ still\n */ b / c /**/ d";
+        let tokens = positions(source);
+        let lexemes: Vec<&str> = tokens.iter().map(|t| t.0.as_str()).collect();
+        assert_eq!(lexemes, ["a", "b", "/", "c", "d", ""]);
+        assert_eq!((tokens[1].1, tokens[1].2), (3, 5));
+
+        let errors: Vec<LoxError> = Scanner::new("x;\n /* a /* b */\n y;")
+            .filter_map(Result::err)
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, ErrorCode::UnterminatedComment);
+        assert_eq!((errors[0].line, errors[0].column), (2, 2));
+        assert_eq!(errors[0].span, Some(Span::new(4, 6)));
+    }
+
+    #[test]
+    fn test_line_comment_ends_before_crlf() {
+        let tokens: Vec<(TokenType, String)> = Scanner::with_trivia("// a\r\n// b\rc\r\nx")
+            .map(|t| t.unwrap())
+            .map(|t| (t.get_token_type(), t.get_lexeme().to_string()))
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                (TokenType::Comment, "// a".to_string()),
+                (TokenType::Whitespace, "\r\n".to_string()),
+                // A lone '\r' isn't a line ending.
+                (TokenType::Comment, "// b\rc".to_string()),
+                (TokenType::Whitespace, "\r\n".to_string()),
+                (TokenType::Identifier, "x".to_string()),
+                (TokenType::Eof, "".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_doc_comments_attach_to_next_token() {
+        let source = "///First line\n///  indented \n//// plain\nfun // plain\nf";
+        let docs: Vec<Option<String>> = Scanner::new(source)
+            .map(|t| t.unwrap().get_doc().map(str::to_string))
+            .collect();
+        assert_eq!(docs, [Some("First line\n indented".to_string()), None, None]);
+    }
+
+    #[test]
+    fn test_number_literals() {
+        for (source, value) in [
+            ("0", 0.0),
+            ("007", 7.0),
+            ("12.5", 12.5),
+            ("1_000_000", 1_000_000.0),
+            ("0.000_1", 0.0001),
+            ("1.5e-3", 0.0015),
+            ("2E+2", 200.0),
+            ("1e1_0", 1e10),
+            ("0xff", 255.0),
+            ("0xDead_Beef", 3_735_928_559.0),
+            ("0b1010", 10.0),
+            ("0o755", 493.0),
+        ] {
+            let token = Scanner::new(source).next().unwrap().unwrap();
+            assert_eq!(token.get_literal(), &Some(Literal::Number(value)), "{source}");
+            assert_eq!(token.get_lexeme(), source);
+        }
+        // A '.' needs a digit after it to start a fraction.
+        let tokens: Vec<String> = Scanner::new("1.foo").map(|t| t.unwrap().get_lexeme().to_string()).collect();
+        assert_eq!(tokens, ["1", ".", "foo", ""]);
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        for (source, message, span) in [
+            ("x = 0x;", "Expect hexadecimal digits after '0x'.", (4, 6)),
+            ("x = 0b102;", "Invalid digit '2' in binary number.", (8, 9)),
+            ("x = 0o8;", "Invalid digit '8' in octal number.", (6, 7)),
+            ("x = 1e;", "Expect digits in exponent.", (5, 6)),
+            ("x = 1.5e+;", "Expect digits in exponent.", (7, 9)),
+            ("x = 1_000_;", "A '_' in a number must be between two digits.", (9, 10)),
+            ("x = 1_.5;", "A '_' in a number must be between two digits.", (5, 6)),
+            ("x = 1__0;", "A '_' in a number must be between two digits.", (5, 6)),
+            ("x = 0x_1;", "A '_' in a number must be between two digits.", (6, 7)),
+            ("x = 0b_;", "A '_' in a number must be between two digits.", (6, 7)),
+            ("x = 12ab;", "Invalid digit 'a' in decimal number.", (6, 7)),
+            ("x = 1e999;", "Number is too large.", (4, 9)),
+        ] {
+            let errors: Vec<LoxError> = Scanner::new(source).filter_map(Result::err).collect();
+            assert_eq!(errors.len(), 1, "{source}");
+            assert_eq!(errors[0].code, ErrorCode::InvalidNumber);
+            assert_eq!(errors[0].message, message, "{source}");
+            assert_eq!(errors[0].span, Some(Span::new(span.0, span.1)), "{source}");
+            assert_eq!(errors[0].column, span.0 + 1, "{source}");
+        }
+
+        // 16^256 is 2^1024, one past the largest f64.
+        let source = format!("x = 0x1{};", "0".repeat(256));
+        let errors: Vec<LoxError> = Scanner::new(&source).filter_map(Result::err).collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Number is too large.");
+        assert_eq!(errors[0].span, Some(Span::new(4, source.len() - 1)));
+    }
+
+    #[test]
+    fn test_identifiers_and_keywords() {
+        let tokens: Vec<(TokenType, String)> = Scanner::new("x1 _private __init__ a_1_b orchid or 1x")
+            .filter_map(Result::ok)
+            .map(|t| (t.get_token_type(), t.get_lexeme().to_string()))
+            .collect();
+        let identifier = |s: &str| (TokenType::Identifier, s.to_string());
+        assert_eq!(
+            tokens,
+            [
+                identifier("x1"),
+                identifier("_private"),
+                identifier("__init__"),
+                identifier("a_1_b"),
+                identifier("orchid"),
+                (TokenType::Or, "or".to_string()),
+                (TokenType::Eof, "".to_string()),
+            ]
+        );
+        // A digit can't start an identifier; `1x` is a malformed number instead.
+        let error = Scanner::new("1x").next().unwrap().unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidNumber);
+    }
+
+    /// Scans 1, 2, 4 and 8 MB of generated Lox and checks the time per megabyte
+    /// stays flat. Run with:
+    ///
+    /// ```text
+    /// cargo test --release -- --ignored --nocapture bench_scanner_scaling
+    /// ```
+    #[test]
+    #[ignore]
+    fn bench_scanner_scaling() {
+        let chunk = "var total = 0;\n\
+            for (var i = 0; i < 10; i = i + 1) {\n\
+            \x20 total = total + i * 2.5; // accumulate\n\
+            \x20 print \"step \" + total;\n\
+            }\n";
+        let mut per_megabyte = Vec::new();
+        for megabytes in [1, 2, 4, 8] {
+            let source = chunk.repeat(megabytes * 1024 * 1024 / chunk.len());
+            // Best of three to smooth out noise from the rest of the machine.
+            let mut best = Duration::MAX;
+            let mut count = 0;
+            for _ in 0..3 {
+                let start = Instant::now();
+                count = Scanner::new(&source).count();
+                best = best.min(start.elapsed());
+            }
+            println!(
+                "{megabytes} MB: {count} tokens in {best:?} ({:.1} MB/s)",
+                megabytes as f64 / best.as_secs_f64()
+            );
+            per_megabyte.push(best.as_secs_f64() / megabytes as f64);
+        }
+        // Quadratic scanning would make the 8 MB run eight times slower per megabyte.
+        assert!(
+            per_megabyte[3] < per_megabyte[0] * 2.0,
+            "scanning is not linear: {per_megabyte:?} seconds per MB"
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum TokenType {
     // Single-character tokens.
@@ -26,6 +28,13 @@ pub enum TokenType {
     // Literals.
     Identifier,
     String,
+    /// The part of a string literal up to a `${`; the interpolated expression's
+    /// tokens follow it, then the rest of the string from the `}` on.
+    Interpolation,
+    /// The text between two interpolated expressions, from `}` to `${`.
+    InterpolationMiddle,
+    /// The end of an interpolated string, from the last `}` to the quote.
+    InterpolationEnd,
     Number,
 
     // Keywords.
@@ -47,6 +56,18 @@ pub enum TokenType {
     While,
 
     Eof,
+
+    // Trivia, only scanned for syntax trees.
+    Whitespace,
+    Comment,
+    /// Source text no token could be scanned from, only in syntax trees.
+    Error,
+}
+
+impl TokenType {
+    pub fn is_trivia(self) -> bool {
+        matches!(self, TokenType::Whitespace | TokenType::Comment)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -55,7 +76,6 @@ pub enum Literal {
     Number(f64),
     Nil,
     Bool(bool),
-    ArithmeticError,
 }
 
 impl std::fmt::Display for Literal {
@@ -71,17 +91,41 @@ impl std::fmt::Display for Literal {
                 }
             }
             Literal::Nil => write!(f, "nil"),
-            _ => panic!("Should not be trying to print this"),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+/// A half-open range of byte offsets into the source text.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Returns the smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+#[derive(Clone)]
 pub struct Token {
     token_type: TokenType,
-    lexeme: String,
+    /// The text the lexeme is part of: the whole source for scanned tokens, so
+    /// that they share it rather than each copying their lexeme out of it.
+    text: Rc<str>,
+    lexeme: Span,
     line: usize,
+    column: usize,
+    span: Span,
     literal: Option<Literal>,
+    /// Text of the `///` comments just before this token, one line per comment.
+    doc: Option<String>,
 }
 
 impl std::fmt::Display for Token {
@@ -89,26 +133,80 @@ impl std::fmt::Display for Token {
         write!(
             f,
             "{:?} {} {:?}",
-            self.token_type, self.lexeme, self.literal
+            self.token_type,
+            self.get_lexeme(),
+            self.literal
         )
     }
 }
 
+impl std::fmt::Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Token")
+            .field("token_type", &self.token_type)
+            .field("lexeme", &self.get_lexeme())
+            .field("line", &self.line)
+            .field("column", &self.column)
+            .field("span", &self.span)
+            .field("literal", &self.literal)
+            .field("doc", &self.doc)
+            .finish()
+    }
+}
+
 impl Token {
     pub fn new(
         token_type: TokenType,
-        lexeme: String,
+        lexeme: &str,
+        literal: Option<Literal>,
+        line: usize,
+    ) -> Self {
+        Self::new_with_span(token_type, lexeme, literal, line, 0, Span::default())
+    }
+
+    /// Creates a token with its own copy of `lexeme`, reported at `span`.
+    /// `column` is 1-based; synthesized tokens use column 0 and an empty span.
+    pub fn new_with_span(
+        token_type: TokenType,
+        lexeme: &str,
         literal: Option<Literal>,
         line: usize,
+        column: usize,
+        span: Span,
+    ) -> Self {
+        Self {
+            lexeme: Span::new(0, lexeme.len()),
+            ..Self::from_source(token_type, lexeme.into(), literal, line, column, span)
+        }
+    }
+
+    /// Creates a token scanned from `span` of `source`, whose lexeme is that
+    /// slice of it.
+    pub fn from_source(
+        token_type: TokenType,
+        source: Rc<str>,
+        literal: Option<Literal>,
+        line: usize,
+        column: usize,
+        span: Span,
     ) -> Self {
         Self {
             token_type,
-            lexeme,
+            text: source,
+            lexeme: span,
             line,
+            column,
+            span,
             literal,
+            doc: None,
         }
     }
 
+    pub fn with_doc(mut self, doc: Option<String>) -> Self {
+        self.doc = doc;
+        self
+    }
+
     pub fn get_token_type(&self) -> TokenType {
         self.token_type
     }
@@ -121,7 +219,19 @@ impl Token {
         self.line
     }
 
-    pub fn get_lexeme(&self) -> String {
-        self.lexeme.clone()
+    pub fn get_column(&self) -> usize {
+        self.column
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+
+    pub fn get_lexeme(&self) -> &str {
+        &self.text[self.lexeme.start..self.lexeme.end]
+    }
+
+    pub fn get_doc(&self) -> Option<&str> {
+        self.doc.as_deref()
     }
 }
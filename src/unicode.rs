@@ -0,0 +1,81 @@
+// Just enough of the Unicode character tables for identifiers and for
+// lining up diagnostics, without pulling in a dependency.
+
+/// Whether `c` is a combining mark (general categories Mn and Mc) from one of
+/// the blocks that exist mostly to hold them, such as U+0301 COMBINING ACUTE
+/// ACCENT in a decomposed "é". Marks scattered through script-specific blocks
+/// are mostly also `char::is_alphabetic`, so identifiers accept them anyway.
+pub fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+/// Whether `c` can start an identifier: '_' or XID_Start, approximated by the
+/// Alphabetic property.
+pub fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+/// Whether `c` can continue an identifier after its first character: XID_Continue,
+/// approximated by letters, digits, '_' and combining marks.
+pub fn is_identifier_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || is_combining_mark(c)
+}
+
+/// How many terminal columns `c` takes up: none for combining marks and
+/// joiners, two for wide East Asian characters and most emoji, one otherwise.
+pub fn display_width(c: char) -> usize {
+    match c {
+        _ if is_combining_mark(c) => 0,
+        '\u{200B}'..='\u{200D}' | '\u{FE00}'..='\u{FE0F}' => 0,
+        '\u{1100}'..='\u{115F}'
+        | '\u{2E80}'..='\u{303E}'
+        | '\u{3041}'..='\u{33FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{A000}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{1F300}'..='\u{1F64F}'
+        | '\u{1F900}'..='\u{1F9FF}'
+        | '\u{20000}'..='\u{3FFFD}' => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identifier_characters() {
+        assert!(is_identifier_start('a'));
+        assert!(is_identifier_start('é'));
+        assert!(is_identifier_start('変'));
+        assert!(is_identifier_start('_'));
+        assert!(!is_identifier_start('1'));
+        assert!(is_identifier_continue('1'));
+        assert!(is_identifier_continue('_'));
+        assert!(!is_identifier_start('\u{0301}'));
+        assert!(!is_identifier_start('👋'));
+        assert!(is_identifier_continue('\u{0301}'));
+        assert!(!is_identifier_continue('👋'));
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width('a'), 1);
+        assert_eq!(display_width('世'), 2);
+        assert_eq!(display_width('👋'), 2);
+        assert_eq!(display_width('\u{0301}'), 0);
+    }
+}
@@ -0,0 +1,115 @@
+use core::fmt;
+use std::rc::Rc;
+
+use crate::{
+    lox_class::LoxClass,
+    lox_function::LoxFunction,
+    lox_instance::LoxInstance,
+    native_functions::NativeFunction,
+    token::Literal,
+};
+
+/// A value produced while running a Lox program. Strings and objects are
+/// reference counted, so cloning a `Value` never copies the underlying data.
+#[derive(Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    Function(Rc<LoxFunction>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<LoxInstance>),
+}
+
+impl Value {
+    /// The name of this value's type, as shown in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Function(_) | Value::Native(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+        }
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::Nil => Value::Nil,
+            Literal::Bool(b) => Value::Bool(*b),
+            Literal::Number(n) => Value::Number(*n),
+            Literal::String(s) => Value::String(Rc::from(s.as_str())),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    /// Values of different types are never equal. Primitives compare by value and
+    /// objects by identity.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Number(n) => write!(f, "{n}"),
+            Value::String(s) => write!(f, "{s}"),
+            Value::Function(function) => write!(f, "{function}"),
+            Value::Native(_) => write!(f, "<native fn>"),
+            Value::Class(klass) => write!(f, "{klass}"),
+            Value::Instance(instance) => write!(f, "{instance}"),
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{s:?}"),
+            _ => write!(f, "{self}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equality() {
+        assert_eq!(Value::Nil, Value::Nil);
+        assert_eq!(Value::from(&Literal::String("a".to_string())), Value::String(Rc::from("a")));
+        assert_ne!(Value::Nil, Value::Bool(false));
+        assert_ne!(Value::Number(0.0), Value::Bool(false));
+        assert_ne!(Value::Number(1.0), Value::String(Rc::from("1")));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Value::Number(3.0).to_string(), "3");
+        assert_eq!(Value::Number(2.5).to_string(), "2.5");
+        assert_eq!(Value::Bool(true).to_string(), "true");
+        assert_eq!(Value::Nil.to_string(), "nil");
+        assert_eq!(Value::String(Rc::from("hi")).to_string(), "hi");
+    }
+}